@@ -6,7 +6,7 @@ use core_wfc::{
 };
 use csv::Writer;
 use grid_wfc::{
-    grid_graph::{self, GridGraphSettings},
+    grid_graph::{self, Borders, GridGraphSettings},
     mxgmn_tileset::MxgmnTileset,
 };
 use rand::Rng;
@@ -40,6 +40,8 @@ fn main() {
                     height: size,
                     width: size,
                     periodic: false,
+                    diagonals: false,
+                    borders: Borders::default(),
                 };
                 let filled = WaveFunction::filled(tileset.tile_count());
                 let graph = grid_graph::create(&settings, filled);
@@ -9,7 +9,7 @@ use csv::Writer;
 use grid_wfc::{
     basic_tileset::BasicTileset,
     carcassonne_tileset::CarcassonneTileset,
-    grid_graph::{self, GridGraphSettings},
+    grid_graph::{self, Borders, GridGraphSettings},
     mxgmn_tileset::MxgmnTileset,
     single_shot,
     world::{ChunkSettings, GenerationMode},
@@ -75,6 +75,8 @@ fn main() {
                                 height: size,
                                 width: size,
                                 periodic: false,
+                                diagonals: false,
+                                borders: Borders::default(),
                             };
                             let filled = WaveFunction::filled(tileset.tile_count());
                             let graph = grid_graph::create(&settings, filled);
@@ -118,6 +120,8 @@ fn main() {
                                 height: size,
                                 width: size,
                                 periodic: false,
+                                diagonals: false,
+                                borders: Borders::default(),
                             };
 
                             let generation_mode = match *generation_type {
@@ -4,7 +4,7 @@ use core_wfc::{
     TileSet, WaveFunction, WfcTask,
 };
 use grid_wfc::{
-    grid_graph::GridGraphSettings,
+    grid_graph::{Borders, GridGraphSettings},
     mxgmn_tileset::MxgmnTileset,
     single_shot,
     world::{ChunkMerging, ChunkSettings, GenerationMode},
@@ -55,6 +55,8 @@ fn main() {
                     height: SIZE,
                     width: SIZE,
                     periodic: false,
+                    diagonals: false,
+                    borders: Borders::default(),
                 };
 
                 let merging = match method {
@@ -100,6 +102,8 @@ fn main() {
                     height: SIZE,
                     width: SIZE,
                     periodic: false,
+                    diagonals: false,
+                    borders: Borders::default(),
                 };
 
                 let backend: &mut dyn Backend = &mut backend;
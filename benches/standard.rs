@@ -3,7 +3,7 @@ use core_wfc::{
 };
 use criterion::{criterion_group, criterion_main, Criterion};
 use grid_wfc::{
-    grid_graph::{self, GridGraphSettings},
+    grid_graph::{self, Borders, GridGraphSettings},
     mxgmn_tileset::MxgmnTileset,
 };
 use rand::Rng;
@@ -20,6 +20,8 @@ pub fn criterion_benchmark(c: &mut Criterion) {
                 height: 64,
                 width: 64,
                 periodic: false,
+                diagonals: false,
+                borders: Borders::default(),
             };
             let filled = WaveFunction::filled(tileset.tile_count());
             let graph = grid_graph::create(&settings, filled);
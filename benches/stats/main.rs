@@ -5,9 +5,9 @@ use core_wfc::{
 };
 use grid_wfc::{
     carcassonne_tileset::CarcassonneTileset,
-    grid_graph::GridGraphSettings,
+    grid_graph::{Borders, GridGraphSettings},
     mxgmn_tileset::MxgmnTileset,
-    world::{ChunkMerging, ChunkSettings, GenerationMode},
+    world::{ChunkMerging, ChunkRecovery, ChunkSettings, GenerationMode},
 };
 use std::{cell::RefCell, path::Path, rc::Rc, sync::Arc};
 
@@ -32,12 +32,19 @@ const GRID_GRAPH_SETTINGS: GridGraphSettings = GridGraphSettings {
     height: SIZE,
     width: SIZE,
     periodic: false,
+    diagonals: false,
+    borders: Borders::FREE,
 };
 const CHUNK_SETTINGS: ChunkSettings = ChunkSettings {
     size: 32,
     overlap: 4,
     discard: 2,
     merging: ChunkMerging::Mixed,
+    recovery: ChunkRecovery {
+        retries: 0,
+        enlarge_discard: 0,
+        rollbacks: 0,
+    },
 };
 const WFC_SETTINGS: WfcSettings = WfcSettings {
     backtracking: BacktrackingSettings::Enabled {
@@ -47,6 +54,9 @@ const WFC_SETTINGS: WfcSettings = WfcSettings {
     entropy: Entropy::Shannon,
     progress_updates: None,
     timeout: None,
+    counts: Vec::new(),
+    path: None,
+    groups_first: false,
 };
 
 const SINGLE_SETTINGS: SingleSettings = SingleSettings {
@@ -56,6 +66,8 @@ const SINGLE_SETTINGS: SingleSettings = SingleSettings {
         height: SIZE,
         width: SIZE,
         periodic: false,
+        diagonals: false,
+        borders: Borders::FREE,
     },
 };
 
@@ -0,0 +1,392 @@
+use crate::{
+    wfc_backend::Backend,
+    wfc_task::{Metadata, TileCount, WfcSettings},
+    *,
+};
+use anyhow::{anyhow, Result};
+use bevy::prelude::*;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use std::{collections::VecDeque, sync::Arc};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Reflect)]
+pub struct PartitionSettings {
+    /// Number of edges around a cluster that are generated with it and merged into the graph
+    pub overlap: usize,
+    /// Number of edges beyond the overlap that are generated but not merged
+    pub discard: usize,
+}
+
+impl Default for PartitionSettings {
+    fn default() -> Self {
+        Self {
+            overlap: 3,
+            discard: 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ClusterState {
+    Scheduled,
+    Done,
+    Failed,
+}
+
+/// Metadata of every task queued by a `GraphChunker`
+pub struct ClusterTaskData {
+    pub cluster: usize,
+}
+
+/// Generates any graph in chunks given a cluster id for every node, the halo of a cluster is found
+/// by walking `Graph::neighbors` so no particular ordering of the nodes is assumed
+///
+/// Clusters are merged like `ChunkMerging::Mixed` in grid_wfc, nodes of the cluster are
+/// overwritten while nodes in the overlap are only written if they weren't collapsed yet
+pub struct GraphChunker {
+    pub graph: Graph<WaveFunction>,
+    /// Cluster id of every node
+    pub partition: Vec<usize>,
+    pub states: Vec<Option<ClusterState>>,
+    pub settings: PartitionSettings,
+    pub tileset: Arc<dyn TileSet>,
+    pub wfc_settings: WfcSettings,
+    pub seed: u64,
+    pub outstanding: usize,
+    clusters: Vec<Vec<usize>>,
+    /// Nodes around every cluster with their distance to it, up to the overlap plus discard
+    halos: Vec<Vec<(usize, usize)>>,
+    /// Clusters whose halos share nodes, they can't be generated at the same time
+    conflicts: Vec<Vec<usize>>,
+    /// Number of conflicts between every cluster and the cluster generation started from
+    distances: Vec<Option<usize>>,
+    /// Tiles of the graph before generating, the nodes of a cluster start from these so tiles a
+    /// neighbor merged into its overlap don't carry over
+    initial: Vec<WaveFunction>,
+    rng: SmallRng,
+    failed: bool,
+}
+
+impl GraphChunker {
+    pub fn new(
+        graph: Graph<WaveFunction>,
+        partition: Vec<usize>,
+        tileset: Arc<dyn TileSet>,
+        settings: PartitionSettings,
+        wfc_settings: WfcSettings,
+        seed: u64,
+    ) -> Result<Self> {
+        if partition.len() != graph.tiles.len() {
+            return Err(anyhow!(
+                "Partition has {} nodes but the graph has {}",
+                partition.len(),
+                graph.tiles.len()
+            ));
+        }
+        if wfc_settings.path.is_some() {
+            return Err(anyhow!(
+                "The path constraint is not supported by GraphChunker"
+            ));
+        }
+
+        let cluster_count = partition.iter().max().map_or(0, |max| max + 1);
+        let mut clusters = vec![Vec::new(); cluster_count];
+        for (node, cluster) in partition.iter().enumerate() {
+            clusters[*cluster].push(node);
+        }
+
+        let halos: Vec<_> = clusters
+            .iter()
+            .map(|nodes| halo(&graph, nodes, settings.overlap + settings.discard))
+            .collect();
+
+        // clusters writing to the same nodes would overwrite each others overlap
+        let mut covering = vec![Vec::new(); graph.tiles.len()];
+        for (cluster, halo) in halos.iter().enumerate() {
+            for (node, _) in halo {
+                covering[*node].push(cluster);
+            }
+        }
+        let mut conflicts = vec![Vec::new(); cluster_count];
+        for clusters in covering {
+            for a in clusters.iter() {
+                conflicts[*a].extend(clusters.iter().filter(|b| *b != a));
+            }
+        }
+        for conflicts in conflicts.iter_mut() {
+            conflicts.sort_unstable();
+            conflicts.dedup();
+        }
+
+        Ok(Self {
+            initial: graph.tiles.clone(),
+            graph,
+            partition,
+            states: vec![None; cluster_count],
+            settings,
+            tileset,
+            wfc_settings,
+            seed,
+            outstanding: 0,
+            clusters,
+            halos,
+            conflicts,
+            distances: vec![None; cluster_count],
+            rng: SmallRng::seed_from_u64(seed),
+            failed: false,
+        })
+    }
+
+    /// Queues a random cluster, the rest of the graph grows outwards from it
+    pub fn start(&mut self, backend: &mut dyn Backend) -> Result<()> {
+        let pending = self.pending();
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let start = pending[self.rng.gen_range(0..pending.len())];
+
+        // clusters are generated in rings around the start so none of them end up enclosed by
+        // clusters that were generated independently
+        self.distances[start] = Some(0);
+        let mut queue = VecDeque::from([start]);
+        while let Some(cluster) = queue.pop_front() {
+            let next = self.distances[cluster].unwrap() + 1;
+            for other in self.conflicts[cluster].iter() {
+                if self.distances[*other].is_none() {
+                    self.distances[*other] = Some(next);
+                    queue.push_back(*other);
+                }
+            }
+        }
+
+        self.queue_cluster(backend, start)
+    }
+
+    /// Returns true once a cluster failed, no new clusters are queued after that
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Merges every finished cluster without blocking and queues the clusters that became ready,
+    /// returns the number of clusters that finished
+    pub fn poll(&mut self, backend: &mut dyn Backend) -> Result<usize> {
+        let mut finished = 0;
+        while let Some((task, result)) = backend.get_output() {
+            self.finish_task(backend, task, result)?;
+            finished += 1;
+        }
+        Ok(finished)
+    }
+
+    /// Blocks until every cluster is generated, fails if any of the clusters failed
+    pub fn run_to_completion(&mut self, backend: &mut dyn Backend) -> Result<()> {
+        while self.outstanding > 0 {
+            let (task, result) = backend.wait_for_output();
+            self.finish_task(backend, task, result)?;
+        }
+
+        if self.failed {
+            Err(anyhow!("Failed to generate graph"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Extracts a cluster with its halo, the nodes of the cluster start from the tiles the graph had
+    /// before generating so restricted nodes stay restricted, the halo is copied from the graph
+    pub fn extract_cluster(&self, cluster: usize) -> Graph<WaveFunction> {
+        let halo = &self.halos[cluster];
+
+        let mut local = vec![None; self.graph.tiles.len()];
+        for (i, (node, _)) in halo.iter().enumerate() {
+            local[*node] = Some(i);
+        }
+
+        let mut tiles = Vec::with_capacity(halo.len());
+        let mut neighbors = Vec::with_capacity(halo.len());
+        for (node, distance) in halo {
+            tiles.push(match distance {
+                0 => self.initial[*node].clone(),
+                _ => self.graph.tiles[*node].clone(),
+            });
+            neighbors.push(
+                self.graph.neighbors[*node]
+                    .iter()
+                    .filter_map(|neighbor| {
+                        local[neighbor.index].map(|index| Neighbor {
+                            direction: neighbor.direction,
+                            index,
+                        })
+                    })
+                    .collect(),
+            );
+        }
+
+        Graph { tiles, neighbors }
+    }
+
+    /// Splits the tile counts of `wfc_settings` between the clusters like `World::chunk_counts`
+    /// in grid_wfc, every cluster gets its share of the minimum and maximum and only counts its own
+    /// nodes
+    pub fn cluster_counts(&self, cluster: usize) -> Vec<TileCount> {
+        let clusters: Vec<usize> = (0..self.clusters.len())
+            .filter(|cluster| !self.clusters[*cluster].is_empty())
+            .collect();
+        let total = clusters.len();
+        // which clusters get the remainders depends on the seed
+        let position = clusters.iter().position(|other| *other == cluster).unwrap();
+        let index = (position + (self.seed % total as u64) as usize) % total;
+
+        let nodes: Vec<usize> = self.halos[cluster]
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, distance))| *distance == 0)
+            .map(|(local, _)| local)
+            .collect();
+        self.wfc_settings
+            .counts
+            .iter()
+            .map(|count| count.share(index, total, nodes.clone()))
+            .collect()
+    }
+
+    /// Merges a cluster extracted with `extract_cluster` back into the graph, the discard is
+    /// skipped
+    pub fn merge_cluster(&mut self, cluster: usize, graph: Graph<WaveFunction>) {
+        for ((node, distance), tile) in self.halos[cluster].iter().zip(graph.tiles) {
+            if *distance > self.settings.overlap {
+                continue;
+            }
+
+            let current = &self.graph.tiles[*node];
+            if *distance == 0 || current.count_bits() > 1 || tile.count_bits() == 0 {
+                self.graph.tiles[*node] = tile;
+            }
+        }
+    }
+
+    fn pending(&self) -> Vec<usize> {
+        (0..self.clusters.len())
+            .filter(|cluster| {
+                self.states[*cluster].is_none() && !self.clusters[*cluster].is_empty()
+            })
+            .collect()
+    }
+
+    /// A cluster is ready once none of the clusters it conflicts with are generating and the ones
+    /// closer to the start are done
+    fn is_ready(&self, cluster: usize) -> bool {
+        let distance = self.distances[cluster];
+        self.states[cluster].is_none()
+            && !self.clusters[cluster].is_empty()
+            && self.conflicts[cluster].iter().all(|other| {
+                let state = self.states[*other];
+                state != Some(ClusterState::Scheduled)
+                    && (self.distances[*other] >= distance || state == Some(ClusterState::Done))
+            })
+    }
+
+    fn queue_cluster(&mut self, backend: &mut dyn Backend, cluster: usize) -> Result<()> {
+        self.states[cluster] = Some(ClusterState::Scheduled);
+        let metadata: Metadata = Some(Arc::new(ClusterTaskData { cluster }));
+        let task = WfcTask {
+            graph: self.extract_cluster(cluster),
+            tileset: self.tileset.clone(),
+            seed: cluster_seed(self.seed, cluster),
+            metadata,
+            settings: WfcSettings {
+                counts: self.cluster_counts(cluster),
+                ..self.wfc_settings.clone()
+            },
+            update_channel: None,
+        };
+
+        self.outstanding += 1;
+        backend.queue_task(task)
+    }
+
+    fn finish_task(
+        &mut self,
+        backend: &mut dyn Backend,
+        task: WfcTask,
+        result: Result<()>,
+    ) -> Result<()> {
+        self.outstanding -= 1;
+
+        // clusters that were still generating when another cluster failed are ignored
+        if self.failed {
+            return Ok(());
+        }
+
+        let cluster = task
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.downcast_ref::<ClusterTaskData>())
+            .expect("Task was not queued by a GraphChunker")
+            .cluster;
+
+        self.merge_cluster(cluster, task.graph);
+        if let Err(e) = result {
+            error!("Failed to generate cluster {}: {:?}", cluster, e);
+            self.states[cluster] = Some(ClusterState::Failed);
+            self.failed = true;
+            return Ok(());
+        }
+        self.states[cluster] = Some(ClusterState::Done);
+
+        // only the conflicts of the cluster could have become ready
+        for other in self.conflicts[cluster].clone() {
+            if self.is_ready(other) {
+                self.queue_cluster(backend, other)?;
+            }
+        }
+
+        // disconnected parts of the graph are started separately
+        if self.outstanding == 0 {
+            self.start(backend)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Finds every node within `distance` edges of `nodes` with its distance, nodes are sorted by
+/// index
+fn halo(graph: &Graph<WaveFunction>, nodes: &[usize], distance: usize) -> Vec<(usize, usize)> {
+    let mut distances = vec![None; graph.tiles.len()];
+    let mut queue = VecDeque::new();
+    for node in nodes {
+        distances[*node] = Some(0);
+        queue.push_back(*node);
+    }
+
+    while let Some(node) = queue.pop_front() {
+        let next = distances[node].unwrap() + 1;
+        if next > distance {
+            continue;
+        }
+        for neighbor in graph.neighbors[node].iter() {
+            if distances[neighbor.index].is_none() {
+                distances[neighbor.index] = Some(next);
+                queue.push_back(neighbor.index);
+            }
+        }
+    }
+
+    distances
+        .into_iter()
+        .enumerate()
+        .filter_map(|(node, distance)| distance.map(|distance| (node, distance)))
+        .collect()
+}
+
+/// Derives the seed of a cluster from the graph seed so it doesn't depend on the order clusters
+/// are generated in
+fn cluster_seed(seed: u64, cluster: usize) -> u64 {
+    // splitmix64
+    let mut hash = seed
+        .wrapping_add(cluster as u64)
+        .wrapping_add(0x9e3779b97f4a7c15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
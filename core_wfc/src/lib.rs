@@ -2,6 +2,8 @@ pub use tileset::*;
 pub use wfc_graph::*;
 pub use wfc_task::WfcTask;
 
+pub mod graph_chunker;
+pub mod path_constraint;
 pub mod tileset;
 pub mod wfc_backend;
 pub mod wfc_graph;
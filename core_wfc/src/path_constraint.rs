@@ -0,0 +1,148 @@
+use crate::{wfc_task::GlobalConstraint, Graph, WaveFunction};
+use anyhow::{anyhow, Result};
+use bevy::prelude::*;
+
+/// Global constraint that keeps walkable tiles connected, based on the path constraint of
+/// DeBroglie
+///
+/// Nodes that can still be walkable form a graph, the constraint fails once nodes that have to be
+/// connected end up in different parts of it and forces the nodes that would split it to be
+/// walkable
+#[derive(Clone, Debug, PartialEq, Reflect)]
+#[reflect(Default)]
+pub struct PathConstraint {
+    /// Tiles that can be walked on
+    pub walkable: Vec<usize>,
+    /// Nodes that have to be connected to each other, when empty every walkable node has to be
+    /// connected instead
+    pub endpoints: Vec<usize>,
+    /// Only neighbors in directions below this connect nodes, 4 connects the sides of grid cells
+    pub directions: usize,
+}
+
+impl Default for PathConstraint {
+    fn default() -> Self {
+        Self {
+            walkable: Vec::new(),
+            endpoints: Vec::new(),
+            directions: 4,
+        }
+    }
+}
+
+impl GlobalConstraint for PathConstraint {
+    fn apply(&self, graph: &mut Graph<WaveFunction>) -> Result<Vec<usize>> {
+        let mut walkable = WaveFunction::empty();
+        for &tile in self.walkable.iter() {
+            walkable.add_tile(tile);
+        }
+
+        // nodes that may be walkable and nodes that have to be connected
+        let count = graph.tiles.len();
+        let mut open = vec![false; count];
+        let mut required = vec![false; count];
+        for node in 0..count {
+            let tiles = &graph.tiles[node];
+            let in_walkable = WaveFunction::intersect(tiles, &walkable).count_bits();
+            open[node] = in_walkable > 0;
+            if self.endpoints.is_empty() {
+                required[node] = in_walkable > 0 && in_walkable == tiles.count_bits();
+            }
+        }
+
+        let mut changed = Vec::new();
+        for &node in self.endpoints.iter() {
+            if !open[node] {
+                return Err(anyhow!("Path endpoint {} can't be walkable", node));
+            }
+            required[node] = true;
+            let tiles = WaveFunction::intersect(&graph.tiles[node], &walkable);
+            if tiles != graph.tiles[node] {
+                graph.tiles[node] = tiles;
+                changed.push(node);
+            }
+        }
+
+        let root = match (0..count).find(|&node| required[node]) {
+            Some(root) => root,
+            None => return Ok(changed),
+        };
+        let total = required.iter().filter(|r| **r).count();
+
+        // depth first search from the root over open nodes to find the nodes that split it, the
+        // search is iterative as the recursion can get as deep as the graph is large
+        let mut discovered = vec![usize::MAX; count];
+        let mut low = vec![0; count];
+        // required nodes in the subtree below every node
+        let mut below = vec![0; count];
+        // subtrees with required nodes that get cut off when a node is removed
+        let mut cut_pieces = vec![0; count];
+        let mut cut_required = vec![0; count];
+
+        let mut time = 1;
+        discovered[root] = 0;
+        below[root] = 1;
+        let mut stack = vec![(root, 0)];
+        while let Some(&(node, next)) = stack.last() {
+            let neighbors = &graph.neighbors[node];
+            if next < neighbors.len() {
+                stack.last_mut().unwrap().1 += 1;
+                let neighbor = neighbors[next];
+                if neighbor.direction >= self.directions || !open[neighbor.index] {
+                    continue;
+                }
+
+                let neighbor = neighbor.index;
+                if discovered[neighbor] == usize::MAX {
+                    discovered[neighbor] = time;
+                    low[neighbor] = time;
+                    time += 1;
+                    below[neighbor] = required[neighbor] as usize;
+                    stack.push((neighbor, 0));
+                } else {
+                    low[node] = low[node].min(discovered[neighbor]);
+                }
+                continue;
+            }
+
+            stack.pop();
+            if let Some(&(parent, _)) = stack.last() {
+                low[parent] = low[parent].min(low[node]);
+                below[parent] += below[node];
+                if low[node] >= discovered[parent] && below[node] > 0 {
+                    cut_pieces[parent] += 1;
+                    cut_required[parent] += below[node];
+                }
+            }
+        }
+
+        if let Some(node) = (0..count).find(|&n| required[n] && discovered[n] == usize::MAX) {
+            return Err(anyhow!("Path can't connect node {} to node {}", root, node));
+        }
+
+        for node in 0..count {
+            if required[node] || discovered[node] == usize::MAX {
+                continue;
+            }
+
+            // the part of the graph above the node is cut off as well
+            let pieces = cut_pieces[node] + (cut_required[node] < total) as usize;
+            if pieces >= 2 {
+                graph.tiles[node] = WaveFunction::intersect(&graph.tiles[node], &walkable);
+                changed.push(node);
+            }
+        }
+
+        // walkable nodes that can't be reached would form a second component
+        if self.endpoints.is_empty() {
+            for node in 0..count {
+                if open[node] && discovered[node] == usize::MAX {
+                    graph.tiles[node] = WaveFunction::difference(&graph.tiles[node], &walkable);
+                    changed.push(node);
+                }
+            }
+        }
+
+        Ok(changed)
+    }
+}
@@ -1,7 +1,18 @@
 use crate::wfc_graph::WaveFunction;
 use bevy::prelude::*;
 use dyn_clone::DynClone;
-use std::{any::Any, sync::Arc};
+use std::{any::Any, collections::BTreeMap, sync::Arc};
+
+/// Node of a semantic hierarchy over the tiles, like `road` containing straight roads, corners
+/// and crossings
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileGroup {
+    pub name: String,
+    /// Index of the enclosing group, groups come after their parent
+    pub parent: Option<usize>,
+    /// Every tile in the group, including the tiles of its children
+    pub tiles: WaveFunction,
+}
 
 #[derive(Debug, Clone)]
 pub enum TileRender {
@@ -18,6 +29,27 @@ pub trait TileSet: DynClone + Send + Sync {
         tile
     }
     fn get_render_tile_assets(&self) -> Vec<(TileRender, Transform)>;
+    /// Named groups of tiles, like `water` or `road`
+    fn get_tags(&self) -> Arc<BTreeMap<String, WaveFunction>> {
+        Arc::default()
+    }
+    /// Tiles with the tag, empty for unknown tags
+    fn tag_mask(&self, tag: &str) -> WaveFunction {
+        self.get_tags()
+            .get(tag)
+            .cloned()
+            .unwrap_or_else(WaveFunction::empty)
+    }
+    /// Semantic hierarchy over the tiles, the solver can collapse cells to these before picking a
+    /// tile, see `WfcSettings::groups_first`
+    fn get_groups(&self) -> Arc<Vec<TileGroup>> {
+        Arc::default()
+    }
+    /// Tiles that need a neighbor in each direction, like the parts of big tiles, the solver
+    /// removes them from nodes without a neighbor in that direction
+    fn get_needs_neighbor(&self) -> Arc<Vec<WaveFunction>> {
+        Arc::default()
+    }
     fn as_any(&self) -> &dyn Any;
 }
 
@@ -1,6 +1,6 @@
 use super::Backend;
 use crate::{
-    wfc_task::{BacktrackingHeuristic, BacktrackingSettings},
+    wfc_task::{BacktrackingHeuristic, BacktrackingSettings, InvalidInitialState},
     WaveFunction, WfcTask,
 };
 use anyhow::{anyhow, Result};
@@ -65,9 +65,21 @@ impl SingleThreaded {
         let start_time = Instant::now();
         let mut last_update = Instant::now();
 
-        // store initial state of all cells already constrained
+        // store initial state of all cells already constrained, along with the group a cell was
+        // narrowed to when groups are collapsed first
         let mut history = Vec::new();
 
+        // parts of big tiles can't face the edge of the graph
+        task.apply_needs_neighbor();
+
+        // cells painted with tiles that don't fit anything can start out empty
+        if let Some(node) = task.graph.tiles.iter().position(|t| t.count_bits() == 0) {
+            return Err(InvalidInitialState { node }.into());
+        }
+
+        // backtracking resets cells to this instead of all tiles, to keep pre-constraints
+        let initial_tiles = task.graph.tiles.clone();
+
         let mut initial = true;
         let mut stack: Vec<usize> = (0..task.graph.tiles.len()).collect();
         loop {
@@ -82,11 +94,14 @@ impl SingleThreaded {
                         let bits = task.graph.tiles[neighbor.index].count_bits();
                         if bits == 1 && task.settings.backtracking != BacktrackingSettings::Disabled
                         {
-                            history.push((neighbor.index, WaveFunction::empty()));
+                            history.push((neighbor.index, WaveFunction::empty(), None));
                         }
                         if bits == 0 {
                             if initial {
-                                return Err(anyhow!("Invalid initial state"));
+                                return Err(InvalidInitialState {
+                                    node: neighbor.index,
+                                }
+                                .into());
                             }
 
                             if task.settings.backtracking == BacktrackingSettings::Disabled {
@@ -94,28 +109,65 @@ impl SingleThreaded {
                             }
 
                             // contradiction found
-                            stack = Self::backtrack(&mut history, task)?;
+                            stack = Self::backtrack(&mut history, &initial_tiles, task)?;
                             break;
                         }
                     }
                 }
             }
 
+            // global constraints are enforced once local propagation settled
+            if !task.settings.constraints().is_empty() {
+                match task.apply_global_constraints() {
+                    Ok(changed) if !changed.is_empty() => {
+                        for &node in changed.iter() {
+                            if task.graph.tiles[node].count_bits() == 1
+                                && task.settings.backtracking != BacktrackingSettings::Disabled
+                            {
+                                history.push((node, WaveFunction::empty(), None));
+                            }
+                        }
+                        stack = changed;
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        if initial {
+                            return Err(anyhow!("Invalid initial state: {}", e));
+                        }
+
+                        if task.settings.backtracking == BacktrackingSettings::Disabled {
+                            return Err(e);
+                        }
+
+                        stack = Self::backtrack(&mut history, &initial_tiles, task)?;
+                        continue;
+                    }
+                }
+            }
+
             initial = false;
 
             if let Some(cell) = task.lowest_entropy(&mut rng) {
                 let mut options = task.graph.tiles[cell].clone();
 
-                // collapse cell
-                task.graph.tiles[cell]
-                    .select_random(&mut rng, &weights)
-                    .unwrap();
+                // collapse cell, or only narrow it to a group
+                let group = match task.settings.groups_first {
+                    true => task.select_group(cell, &mut rng),
+                    false => None,
+                };
+                match &group {
+                    Some(group) => task.graph.tiles[cell] = group.clone(),
+                    None => task.graph.tiles[cell]
+                        .select_random(&mut rng, &weights)
+                        .unwrap(),
+                }
                 stack.push(cell);
 
                 // if we backtrack to this cell the option we just selected will be removed
                 options = WaveFunction::difference(&options, &task.graph.tiles[cell]);
                 if task.settings.backtracking != BacktrackingSettings::Disabled {
-                    history.push((cell, options));
+                    history.push((cell, options, group));
                 }
             } else {
                 // all cells collapsed
@@ -143,7 +195,8 @@ impl SingleThreaded {
     }
 
     fn backtrack(
-        history: &mut Vec<(usize, WaveFunction)>,
+        history: &mut Vec<(usize, WaveFunction, Option<WaveFunction>)>,
+        initial_tiles: &[WaveFunction],
         task: &mut WfcTask,
     ) -> Result<Vec<usize>> {
         let (heuristic, restarts_left) = match &mut task.settings.backtracking {
@@ -164,10 +217,9 @@ impl SingleThreaded {
         }
 
         // unconstrain all tiles which are not fully collapsed
-        let filled = WaveFunction::filled(task.tileset.tile_count());
-        for i in 0..task.graph.tiles.len() {
-            if task.graph.tiles[i].count_bits() != 1 {
-                task.graph.tiles[i] = filled.clone();
+        for (tiles, initial) in task.graph.tiles.iter_mut().zip(initial_tiles) {
+            if tiles.count_bits() != 1 {
+                *tiles = initial.clone();
             }
         }
 
@@ -181,7 +233,7 @@ impl SingleThreaded {
             }
             BacktrackingHeuristic::Degree { degree } => {
                 let mut steps = history.len();
-                for (index, (_, options)) in history.iter().rev().enumerate() {
+                for (index, (_, options, _)) in history.iter().rev().enumerate() {
                     if options.count_bits() >= *degree {
                         steps = index;
                         break;
@@ -193,12 +245,12 @@ impl SingleThreaded {
 
         // step back till we find a cell with more than one option
         loop {
-            let (index, options) = history
+            let (index, options, _) = history
                 .pop()
                 .ok_or(anyhow!("Ran out of options when backtracking"))?;
 
             // clear cell
-            task.graph.tiles[index] = filled.clone();
+            task.graph.tiles[index] = initial_tiles[index].clone();
 
             if history.is_empty() {
                 // we have backtracked to the initial state, this is a random restart
@@ -209,7 +261,7 @@ impl SingleThreaded {
             if options.count_bits() > 0 && steps == 0 {
                 // this is to allow the cell to be backtracked past again
                 if options.count_bits() == 1 {
-                    history.push((index, WaveFunction::empty()));
+                    history.push((index, WaveFunction::empty(), None));
                 }
                 task.graph.tiles[index] = options;
                 break;
@@ -220,6 +272,15 @@ impl SingleThreaded {
             }
         }
 
+        // cells narrowed to a group were reset with the rest, narrow the ones still in the history
+        // again so their choice isn't lost
+        for (index, _, group) in history.iter() {
+            if let Some(group) = group {
+                let tiles = &mut task.graph.tiles[*index];
+                *tiles = WaveFunction::intersect(tiles, group);
+            }
+        }
+
         // re-propagate changes
         return Ok((0..task.graph.tiles.len())
             .filter(|i| task.graph.tiles[*i].count_bits() < task.tileset.tile_count())
@@ -1,7 +1,8 @@
-use crate::{tileset::*, wfc_graph::*};
+use crate::{path_constraint::PathConstraint, tileset::*, wfc_graph::*};
+use anyhow::{anyhow, Result};
 use bevy::prelude::*;
 use crossbeam::channel::Sender;
-use rand::Rng;
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
 use std::{any::Any, sync::Arc, time::Duration};
 
 pub type Metadata = Option<Arc<dyn Any + Send + Sync>>;
@@ -13,6 +14,141 @@ pub struct WfcSettings {
     pub entropy: Entropy,
     pub progress_updates: Option<f64>,
     pub timeout: Option<Duration>,
+    /// Limits on how often groups of tiles appear, a chunked `World` splits these between its
+    /// chunks while other chunked generators apply them to every chunk as is
+    pub counts: Vec<TileCount>,
+    /// Keeps walkable tiles connected, only supported by single tasks so chunked generators
+    /// return an error when it's set
+    pub path: Option<PathConstraint>,
+    /// Collapses cells one level down the tileset's groups at a time, so a cell becomes `road`
+    /// before it becomes a road corner
+    pub groups_first: bool,
+}
+
+/// Constraint on the whole graph, enforced by the solver once local propagation settled
+pub trait GlobalConstraint {
+    /// Returns the nodes that changed or the reason the constraint can't be satisfied anymore
+    fn apply(&self, graph: &mut Graph<WaveFunction>) -> Result<Vec<usize>>;
+}
+
+impl WfcSettings {
+    /// Every global constraint of the settings, in the order they are applied
+    pub fn constraints(&self) -> Vec<&dyn GlobalConstraint> {
+        let mut constraints: Vec<&dyn GlobalConstraint> = Vec::new();
+        for count in self.counts.iter() {
+            constraints.push(count);
+        }
+        if let Some(path) = &self.path {
+            constraints.push(path);
+        }
+        constraints
+    }
+}
+
+/// Global constraint on the number of nodes that end up as one of `tiles`, like exactly one castle
+/// or at least one water tile
+#[derive(Clone, Debug, PartialEq, Reflect)]
+#[reflect(Default)]
+pub struct TileCount {
+    pub tiles: Vec<usize>,
+    pub min: usize,
+    pub max: usize,
+    /// Nodes that are counted, every node is counted when empty
+    pub nodes: Vec<usize>,
+}
+
+impl Default for TileCount {
+    fn default() -> Self {
+        Self {
+            tiles: Vec::new(),
+            min: 0,
+            max: usize::MAX,
+            nodes: Vec::new(),
+        }
+    }
+}
+
+impl TileCount {
+    /// Share `index` out of `total` of the minimum and maximum counting only `nodes`, the shares
+    /// of every index add up to the whole count so a count can be split between chunks
+    pub fn share(&self, index: usize, total: usize, nodes: Vec<usize>) -> Self {
+        let share = |value: usize| match value {
+            usize::MAX => usize::MAX,
+            value => {
+                let (value, index, total) = (value as u128, index as u128, total as u128);
+                (value * (index + 1) / total - value * index / total) as usize
+            }
+        };
+        Self {
+            tiles: self.tiles.clone(),
+            min: share(self.min),
+            max: share(self.max),
+            nodes,
+        }
+    }
+}
+
+impl GlobalConstraint for TileCount {
+    /// Removes the tiles of the count once it reached its maximum and forces every node that can
+    /// still count towards the minimum once all of them are needed
+    fn apply(&self, graph: &mut Graph<WaveFunction>) -> Result<Vec<usize>> {
+        let tiles = &mut graph.tiles;
+        let mut group = WaveFunction::empty();
+        for &tile in self.tiles.iter() {
+            group.add_tile(tile);
+        }
+
+        let nodes: Vec<usize> = match self.nodes.is_empty() {
+            true => (0..tiles.len()).collect(),
+            false => self.nodes.clone(),
+        };
+
+        // nodes that are certainly in the group and nodes that may still be
+        let mut certain = 0;
+        let mut undecided = Vec::new();
+        for &node in nodes.iter() {
+            let in_group = WaveFunction::intersect(&tiles[node], &group).count_bits();
+            if in_group == 0 {
+                continue;
+            }
+            if in_group == tiles[node].count_bits() {
+                certain += 1;
+            } else {
+                undecided.push(node);
+            }
+        }
+
+        if certain > self.max {
+            return Err(anyhow!(
+                "Tile count of {:?} exceeded, {} tiles but at most {} allowed",
+                self.tiles,
+                certain,
+                self.max
+            ));
+        }
+        if certain + undecided.len() < self.min {
+            return Err(anyhow!(
+                "Tile count of {:?} can't be reached, {} tiles possible but at least {} required",
+                self.tiles,
+                certain + undecided.len(),
+                self.min
+            ));
+        }
+
+        if certain == self.max {
+            for &node in undecided.iter() {
+                tiles[node] = WaveFunction::difference(&tiles[node], &group);
+            }
+            Ok(undecided)
+        } else if certain + undecided.len() == self.min {
+            for &node in undecided.iter() {
+                tiles[node] = WaveFunction::intersect(&tiles[node], &group);
+            }
+            Ok(undecided)
+        } else {
+            Ok(Vec::new())
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Reflect)]
@@ -58,6 +194,22 @@ pub enum Entropy {
     Shannon,
 }
 
+/// Error of a task whose cells contradict each other before anything is collapsed, for example
+/// because they were painted with tiles that can't be next to each other
+#[derive(Debug)]
+pub struct InvalidInitialState {
+    /// Node that ran out of possible tiles
+    pub node: usize,
+}
+
+impl std::fmt::Display for InvalidInitialState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid initial state at node {}", self.node)
+    }
+}
+
+impl std::error::Error for InvalidInitialState {}
+
 pub struct WfcTask {
     pub graph: Graph<WaveFunction>,
     pub tileset: Arc<dyn TileSet>,
@@ -68,6 +220,51 @@ pub struct WfcTask {
 }
 
 impl WfcTask {
+    /// Narrows a cell to the next level of the tileset's groups, weighted by the tiles left in each
+    /// child group, tiles outside of every child are grouped together. Returns `None` once the
+    /// cell is below every group and a tile should be picked
+    pub fn select_group<R: Rng>(&self, cell: usize, rng: &mut R) -> Option<WaveFunction> {
+        let groups = self.tileset.get_groups();
+        let weights = self.tileset.get_weights();
+        let options = &self.graph.tiles[cell];
+
+        let mut parent = None;
+        loop {
+            let candidates: Vec<(usize, WaveFunction)> = groups
+                .iter()
+                .enumerate()
+                .filter(|(_, group)| group.parent == parent)
+                .map(|(index, group)| (index, WaveFunction::intersect(&group.tiles, options)))
+                .filter(|(_, tiles)| tiles.count_bits() > 0)
+                .collect();
+            if candidates.is_empty() {
+                return None;
+            }
+
+            // already inside one of the groups, go one level down
+            if let Some((index, _)) = candidates.iter().find(|(_, tiles)| tiles == options) {
+                parent = Some(*index);
+                continue;
+            }
+
+            let covered = candidates
+                .iter()
+                .fold(WaveFunction::empty(), |covered, (_, tiles)| {
+                    WaveFunction::join(&covered, tiles)
+                });
+            let rest = WaveFunction::difference(options, &covered);
+            let mut candidates: Vec<_> = candidates.into_iter().map(|(_, tiles)| tiles).collect();
+            if rest.count_bits() > 0 {
+                candidates.push(rest);
+            }
+            let candidate_weights = candidates
+                .iter()
+                .map(|tiles| tiles.tile_iter().map(|tile| weights[tile]).sum::<f32>());
+            let index = WeightedIndex::new(candidate_weights).ok()?.sample(rng);
+            return Some(candidates.swap_remove(index));
+        }
+    }
+
     /// Todo: Use the weights when calculating the entropy
     pub fn lowest_entropy<R: Rng>(&self, rng: &mut R) -> Option<usize> {
         let weights = self.tileset.get_weights();
@@ -128,6 +325,13 @@ impl WfcTask {
 
         // combine all constraints of all the tiles that the neighbor can be according to what the current tile can be
         let constraints = self.tileset.get_constraints();
+
+        // directions the tileset has no constraints for, like diagonals, are unconstrained
+        let directions = constraints.first().map(|c| c.len()).unwrap_or(0);
+        if neighbor.direction >= directions {
+            return false;
+        }
+
         let mut allowed = WaveFunction::empty();
         for tile in self.graph.tiles[index].tile_iter() {
             allowed = WaveFunction::join(&allowed, &constraints[tile][neighbor.direction]);
@@ -143,6 +347,35 @@ impl WfcTask {
         updated
     }
 
+    /// Removes the tiles that need a neighbor from the nodes without a neighbor in that direction,
+    /// collapsed nodes are left as they are, see `TileSet::get_needs_neighbor`
+    pub fn apply_needs_neighbor(&mut self) {
+        let needs_neighbor = self.tileset.get_needs_neighbor();
+        let nodes = self.graph.tiles.iter_mut().zip(self.graph.neighbors.iter());
+        for (tiles, neighbors) in nodes {
+            if tiles.count_bits() <= 1 {
+                continue;
+            }
+            for (direction, needs) in needs_neighbor.iter().enumerate() {
+                if !neighbors
+                    .iter()
+                    .any(|neighbor| neighbor.direction == direction)
+                {
+                    *tiles = WaveFunction::difference(tiles, needs);
+                }
+            }
+        }
+    }
+
+    /// Applies every constraint of `WfcSettings::constraints`, returns the nodes that changed
+    pub fn apply_global_constraints(&mut self) -> Result<Vec<usize>> {
+        let mut changed = Vec::new();
+        for constraint in self.settings.constraints() {
+            changed.append(&mut constraint.apply(&mut self.graph)?);
+        }
+        Ok(changed)
+    }
+
     pub fn clear(&mut self) {
         for tile in self.graph.tiles.iter_mut() {
             *tile = WaveFunction::filled(self.tileset.tile_count());
@@ -0,0 +1,145 @@
+use crate::grid_graph::Direction;
+use anyhow::{bail, Result};
+use bevy::prelude::*;
+use core_wfc::{TileRender, WaveFunction, TILE_U32S};
+use std::collections::BTreeMap;
+
+/// Tile covering `size` cells, solved as one part per cell. Parts only fit next to the other parts
+/// of the same tile inside the footprint and use the edges of another tile on the outside
+#[derive(Debug, Clone)]
+pub struct BigTile {
+    /// Tag given to all parts of the tile
+    pub name: String,
+    pub size: UVec2,
+    /// Weight of the whole footprint, split evenly between its parts
+    pub weight: f32,
+    /// Tile whose edges each part uses on the outside of the footprint, row by row from the bottom
+    /// left, a single tile is used for every part
+    pub like: Vec<usize>,
+    /// Drawn once over the whole footprint
+    pub render: TileRender,
+}
+
+/// Appends the parts of `big_tiles` to a tileset, with the constraints between them and the
+/// existing tiles derived from the tiles they are `like`. Parts are added to `needs_neighbor` for
+/// the directions the rest of their tile lies in, see `TileSet::get_needs_neighbor`
+///
+/// The constraints have 4 directions, or 8 when the tileset has diagonal constraints as well
+pub fn add_big_tiles(
+    big_tiles: &[BigTile],
+    constraints: &mut Vec<Vec<WaveFunction>>,
+    weights: &mut Vec<f32>,
+    render: &mut Vec<(TileRender, Transform)>,
+    tags: &mut BTreeMap<String, WaveFunction>,
+    needs_neighbor: &mut Vec<WaveFunction>,
+) -> Result<()> {
+    if big_tiles.is_empty() {
+        return Ok(());
+    }
+
+    let small_count = constraints.len();
+    let tile_count = small_count
+        + big_tiles
+            .iter()
+            .map(|big_tile| (big_tile.size.x * big_tile.size.y) as usize)
+            .sum::<usize>();
+    if tile_count > TILE_U32S * 32 {
+        bail!(
+            "{} tiles with the parts of big tiles, at most {} are supported",
+            tile_count,
+            TILE_U32S * 32
+        );
+    }
+
+    let direction_count = constraints.first().map_or(4, Vec::len);
+    if direction_count != 4 && direction_count != 8
+        || constraints.iter().any(|tile| tile.len() != direction_count)
+    {
+        bail!("big tiles need constraints for either 4 or 8 directions for every tile");
+    }
+    needs_neighbor.resize(direction_count, WaveFunction::empty());
+
+    // the tile each part copies its outer edges from and the parts next to it inside the footprint
+    let mut like: Vec<usize> = (0..small_count).collect();
+    let mut inside: Vec<Vec<Option<usize>>> = vec![vec![None; direction_count]; small_count];
+    for big_tile in big_tiles.iter() {
+        let part_count = (big_tile.size.x * big_tile.size.y) as usize;
+        if part_count == 0 {
+            bail!("big tile {} has no parts", big_tile.name);
+        }
+        if big_tile.like.len() != 1 && big_tile.like.len() != part_count {
+            bail!(
+                "big tile {} needs 1 or {} tiles to be like, not {}",
+                big_tile.name,
+                part_count,
+                big_tile.like.len()
+            );
+        }
+        if let Some(&tile) = big_tile.like.iter().find(|&&tile| tile >= small_count) {
+            bail!("big tile {} is like unknown tile {}", big_tile.name, tile);
+        }
+
+        let base = like.len();
+        let size = big_tile.size.as_ivec2();
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let part = (y * size.x + x) as usize;
+                like.push(big_tile.like[part % big_tile.like.len()]);
+
+                let mut neighbors = vec![None; direction_count];
+                for (direction, neighbor) in neighbors.iter_mut().enumerate() {
+                    let pos = IVec2::new(x, y) + Direction::from(direction).to_ivec2();
+                    if pos.cmpge(IVec2::ZERO).all() && pos.cmplt(size).all() {
+                        *neighbor = Some(base + (pos.y * size.x + pos.x) as usize);
+                        needs_neighbor[direction].add_tile(like.len() - 1);
+                    }
+                }
+                inside.push(neighbors);
+                weights.push(big_tile.weight / part_count as f32);
+
+                // the bottom left part draws the whole tile, the rest stay empty
+                render.push(match part {
+                    0 => (
+                        big_tile.render.clone(),
+                        Transform::from_translation(((size - 1).as_vec2() / 2.0).extend(0.0))
+                            .with_scale(size.as_vec2().extend(1.0)),
+                    ),
+                    _ => (TileRender::Color(Color::NONE), Transform::IDENTITY),
+                });
+            }
+        }
+
+        let tag = tags
+            .entry(big_tile.name.clone())
+            .or_insert_with(WaveFunction::empty);
+        for part in base..like.len() {
+            tag.add_tile(part);
+        }
+    }
+
+    let small = std::mem::take(constraints);
+    for (tile, tile_inside) in inside.iter().enumerate() {
+        let mut tile_constraints = Vec::new();
+        for (direction, neighbor) in tile_inside.iter().enumerate() {
+            let mut allowed = WaveFunction::empty();
+            match neighbor {
+                Some(part) => allowed.add_tile(*part),
+                None => {
+                    let other = Direction::from(direction).other() as usize;
+                    for (other_tile, other_inside) in inside.iter().enumerate() {
+                        // the facing edge of the other tile has to be on the outside as well
+                        if other_inside[other].is_none()
+                            && small[like[tile]][direction].contains(like[other_tile])
+                        {
+                            allowed.add_tile(other_tile);
+                        }
+                    }
+                }
+            }
+            tile_constraints.push(allowed);
+        }
+        constraints.push(tile_constraints);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,628 @@
+//! Headless generator for machines without a GPU or display, writes the result to an image or json
+//! file instead of opening the Bevy app. It is part of `grid_wfc` so it builds without the
+//! windowing and rendering dependencies of the app, run it from the root of the repo so `assets`
+//! is found.
+//!
+//! ```bash
+//! $ cargo run --release --manifest-path grid_wfc/Cargo.toml -- generate \
+//!     --tileset assets/mxgmn/Summer.xml --size 128x128 --seed 42 --chunked --chunk-size 16 \
+//!     --overlap 3 --out out.png
+//! $ cargo run --release --manifest-path grid_wfc/Cargo.toml -- generate --tileset voxel \
+//!     --size 32x16x32 --chunked --out out.vox
+//! ```
+
+use anyhow::{anyhow, Result};
+use bevy::{
+    math::{EulerRot, IVec2, IVec3, Vec4},
+    transform::components::Transform,
+};
+use core_wfc::{
+    path_constraint::PathConstraint,
+    wfc_backend::{Backend, MultiThreaded, SingleThreaded},
+    wfc_task::{BacktrackingSettings, TileCount, WfcSettings},
+    Graph, TileRender, TileSet, WaveFunction, WfcTask,
+};
+use grid_wfc::{
+    basic_tileset::BasicTileset,
+    carcassonne_tileset::CarcassonneTileset,
+    grid_graph::{self, Border, Borders, GridGraphSettings},
+    mxgmn_tileset::MxgmnTileset,
+    overlapping_tileset::{OverlappingSettings, OverlappingTileset},
+    single_shot,
+    voxel_tileset::VoxelTileset,
+    world::{ChunkSettings, GenerationMode},
+    world3d::World3d,
+};
+use image::{imageops, Rgba, RgbaImage};
+use std::{
+    f32::consts::FRAC_PI_2,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const USAGE: &str = "Usage: hwfc generate --tileset <carcassonne|basic|voxel|PATH.xml|PATH.png> --out <PATH.png|PATH.json|PATH.vox> [OPTIONS]
+
+Options:
+    --size <WxH[xD]>        Output size in tiles, volumes are saved as .vox [default: 64x64]
+    --seed <SEED>           Seed used for generation [default: random]
+    --periodic              Constrain opposite edges so the output tiles, --chunked also needs
+                            --deterministic and an even number of chunks along each axis
+    --diagonals             Connect cells diagonally as well
+    --no-backtracking       Fail on the first contradiction instead of backtracking
+    --groups-first          Collapse cells to the groups of a mxgmn tileset before picking tiles
+    --count <TILES:MIN:MAX> Limit how often tiles appear, TILES is comma separated and MIN or MAX
+                            can be left empty or given in percent of the output, like 3,4::10%
+    --walkable <TILES>      Keep every cell rendered as one of TILES connected, not supported with
+                            --chunked, TILES of both can also be tags of the tileset like water
+    --weight <TILES:FACTOR> Multiply the weights of TILES by FACTOR, like water:2
+    --border <EDGES:TILES>  Restrict the cells along EDGES to TILES, EDGES is a comma separated list
+                            of left, right, bottom, top or all
+    --outside <EDGES:TILES> Treat the cells outside of EDGES as one of TILES
+    --subset <NAME>         Subset of a mxgmn tileset
+    --n <N>                 Pattern size of overlapping samples [default: 3]
+    --symmetry <S>          Symmetry of overlapping samples [default: 8]
+    --ground                Fix the bottom row of overlapping samples to the ground pattern
+    --non-periodic-input    Do not wrap overlapping samples when extracting patterns
+    --chunked               Use chunked generation
+    --deterministic         Use the deterministic variant of chunked generation
+    --chunk-size <SIZE>     Size of chunks [default: 16]
+    --overlap <OVERLAP>     Padding written back to the world by each chunk [default: 3]
+    --discard <DISCARD>     Padding generated but not written back by each chunk [default: 1]
+    --retries <RETRIES>     Times a failed chunk is generated again with a new seed [default: 0]
+    --enlarge <DISCARD>     Discard added to a chunk on every retry [default: 0]
+    --rollbacks <ROLLBACKS> Times the neighbors of a failing chunk are generated again [default: 0]
+    --threads <THREADS>     Threads used for chunked generation [default: 1]";
+
+struct Args {
+    tileset: String,
+    out: PathBuf,
+    graph_settings: GridGraphSettings,
+    /// Set for volumes
+    depth: Option<usize>,
+    seed: u64,
+    backtracking: bool,
+    groups_first: bool,
+    /// Unparsed `--count` values, percentages depend on the size
+    counts: Vec<String>,
+    /// Unparsed `--walkable` value, tags depend on the tileset
+    walkable: Option<String>,
+    /// Unparsed `--weight` values, tags depend on the tileset
+    weights: Vec<String>,
+    /// Unparsed `--border` and `--outside` values, set for `--outside`
+    borders: Vec<(String, bool)>,
+    subset: Option<String>,
+    n: usize,
+    symmetry: usize,
+    ground: bool,
+    periodic_input: bool,
+    chunked: bool,
+    deterministic: bool,
+    chunk_settings: ChunkSettings,
+    threads: usize,
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("generate") => generate(&parse_args(args)?),
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(anyhow!("expected a command\n\n{}", USAGE)),
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args> {
+    let mut result = Args {
+        tileset: String::new(),
+        out: PathBuf::new(),
+        graph_settings: GridGraphSettings::default(),
+        depth: None,
+        seed: rand::random(),
+        backtracking: true,
+        groups_first: false,
+        counts: Vec::new(),
+        walkable: None,
+        weights: Vec::new(),
+        borders: Vec::new(),
+        subset: None,
+        n: 3,
+        symmetry: 8,
+        ground: false,
+        periodic_input: true,
+        chunked: false,
+        deterministic: false,
+        chunk_settings: ChunkSettings::default(),
+        threads: 1,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow!("missing value for {}\n\n{}", arg, USAGE))
+        };
+        match arg.as_str() {
+            "--tileset" => result.tileset = value()?,
+            "--out" => result.out = PathBuf::from(value()?),
+            "--size" => {
+                let size = value()?;
+                let sizes = size
+                    .split('x')
+                    .map(|value| value.parse())
+                    .collect::<Result<Vec<usize>, _>>()?;
+                match sizes[..] {
+                    [width, height] | [width, height, _] => {
+                        result.graph_settings.width = width;
+                        result.graph_settings.height = height;
+                        result.depth = sizes.get(2).copied();
+                    }
+                    _ => return Err(anyhow!("invalid size {}, expected WxH or WxHxD", size)),
+                }
+            }
+            "--seed" => result.seed = value()?.parse()?,
+            "--periodic" => result.graph_settings.periodic = true,
+            "--diagonals" => result.graph_settings.diagonals = true,
+            "--no-backtracking" => result.backtracking = false,
+            "--groups-first" => result.groups_first = true,
+            "--count" => result.counts.push(value()?),
+            "--walkable" => result.walkable = Some(value()?),
+            "--weight" => result.weights.push(value()?),
+            "--border" => result.borders.push((value()?, false)),
+            "--outside" => result.borders.push((value()?, true)),
+            "--subset" => result.subset = Some(value()?),
+            "--n" => result.n = value()?.parse()?,
+            "--symmetry" => result.symmetry = value()?.parse()?,
+            "--ground" => result.ground = true,
+            "--non-periodic-input" => result.periodic_input = false,
+            "--chunked" => result.chunked = true,
+            "--deterministic" => result.deterministic = true,
+            "--chunk-size" => result.chunk_settings.size = value()?.parse()?,
+            "--overlap" => result.chunk_settings.overlap = value()?.parse()?,
+            "--discard" => result.chunk_settings.discard = value()?.parse()?,
+            "--retries" => result.chunk_settings.recovery.retries = value()?.parse()?,
+            "--enlarge" => result.chunk_settings.recovery.enlarge_discard = value()?.parse()?,
+            "--rollbacks" => result.chunk_settings.recovery.rollbacks = value()?.parse()?,
+            "--threads" => result.threads = value()?.parse()?,
+            _ => return Err(anyhow!("unknown argument {}\n\n{}", arg, USAGE)),
+        }
+    }
+
+    if result.tileset.is_empty() || result.out.as_os_str().is_empty() {
+        return Err(anyhow!("--tileset and --out are required\n\n{}", USAGE));
+    }
+
+    Ok(result)
+}
+
+/// Parses a comma separated list of tile indices and tags, indices are render tiles when `render` is
+/// set
+fn parse_tiles(tiles: &str, tileset: &dyn TileSet, render: bool) -> Result<Vec<usize>> {
+    let mut mask = WaveFunction::empty();
+    for name in tiles.split(',') {
+        if let Ok(index) = name.parse::<usize>() {
+            for tile in 0..tileset.tile_count() {
+                let tile_index = match render {
+                    true => tileset.get_render_tile(tile),
+                    false => tile,
+                };
+                if tile_index == index {
+                    mask.add_tile(tile);
+                }
+            }
+        } else {
+            let tagged = tileset.tag_mask(name);
+            if tagged.count_bits() == 0 {
+                return Err(anyhow!("unknown tile or tag {}", name));
+            }
+            mask = WaveFunction::join(&mask, &tagged);
+        }
+    }
+    Ok(mask.tile_iter().collect())
+}
+
+/// Parses a `--count` value for an output with `cells` cells
+fn parse_count(count: &str, tileset: &dyn TileSet, cells: usize) -> Result<TileCount> {
+    let parts = count.split(':').collect::<Vec<_>>();
+    let [tiles, min, max] = parts[..] else {
+        return Err(anyhow!("invalid count {}, expected TILES:MIN:MAX", count));
+    };
+    let limit = |value: &str, default: usize| -> Result<usize> {
+        match value.strip_suffix('%') {
+            _ if value.is_empty() => Ok(default),
+            Some(percent) => Ok((percent.parse::<f64>()? / 100.0 * cells as f64) as usize),
+            None => Ok(value.parse()?),
+        }
+    };
+
+    Ok(TileCount {
+        tiles: parse_tiles(tiles, tileset, false)?,
+        min: limit(min, 0)?,
+        max: limit(max, usize::MAX)?,
+        nodes: Vec::new(),
+    })
+}
+
+/// Applies the `--weight` values to the weights of the tileset
+fn apply_weights(weights: &[String], tileset: &mut dyn TileSet) -> Result<()> {
+    let mut result = (*tileset.get_weights()).clone();
+    for value in weights.iter() {
+        let Some((tiles, factor)) = value.rsplit_once(':') else {
+            return Err(anyhow!("invalid weight {}, expected TILES:FACTOR", value));
+        };
+        let factor: f32 = factor.parse()?;
+        for tile in parse_tiles(tiles, tileset, false)? {
+            result[tile] *= factor;
+        }
+    }
+    tileset.set_weights(result);
+    Ok(())
+}
+
+/// Parses the `--border` and `--outside` values, later values replace earlier ones on the same edge
+fn parse_borders(borders: &[(String, bool)], tileset: &dyn TileSet) -> Result<Borders> {
+    let mut result = Borders::default();
+    for (value, outside) in borders.iter() {
+        let Some((edges, tiles)) = value.split_once(':') else {
+            return Err(anyhow!("invalid border {}, expected EDGES:TILES", value));
+        };
+        let tiles = parse_tiles(tiles, tileset, false)?;
+        let border = match outside {
+            true => Border::Outside(tiles),
+            false => Border::Tiles(tiles),
+        };
+        for edge in edges.split(',') {
+            match edge {
+                "left" => result.left = border.clone(),
+                "right" => result.right = border.clone(),
+                "bottom" => result.bottom = border.clone(),
+                "top" => result.top = border.clone(),
+                "all" => result = Borders::all(border.clone()),
+                _ => return Err(anyhow!("unknown edge {}", edge)),
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn load_tileset(args: &Args) -> Result<Arc<dyn TileSet>> {
+    let mut tileset: Box<dyn TileSet> = match args.tileset.as_str() {
+        "carcassonne" => Box::new(CarcassonneTileset::default()),
+        "basic" => Box::new(BasicTileset::default()),
+        "voxel" => Box::new(VoxelTileset::default()),
+        path => {
+            let path = Path::new(path);
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("xml") => Box::new(MxgmnTileset::new(path, args.subset.clone())?),
+                Some("png") => {
+                    let settings = OverlappingSettings {
+                        overlap: args.n / 2,
+                        symmetry: args.symmetry,
+                        periodic_input: args.periodic_input,
+                        ground: args.ground,
+                        full_neighborhood: true,
+                    };
+                    Box::new(OverlappingTileset::from_image(path, &settings))
+                }
+                _ => return Err(anyhow!("unsupported tileset {}", path.display())),
+            }
+        }
+    };
+    apply_weights(&args.weights, tileset.as_mut())?;
+    Ok(Arc::from(tileset))
+}
+
+fn generate(args: &Args) -> Result<()> {
+    let tileset = load_tileset(args)?;
+    let mut settings = args.graph_settings.clone();
+    settings.borders = parse_borders(&args.borders, tileset.as_ref())?;
+    let wfc_settings = WfcSettings {
+        backtracking: match args.backtracking {
+            true => BacktrackingSettings::default(),
+            false => BacktrackingSettings::Disabled,
+        },
+        groups_first: args.groups_first,
+        counts: args
+            .counts
+            .iter()
+            .map(|count| parse_count(count, tileset.as_ref(), settings.width * settings.height))
+            .collect::<Result<_>>()?,
+        path: match &args.walkable {
+            None => None,
+            Some(walkable) => Some(PathConstraint {
+                walkable: parse_tiles(walkable, tileset.as_ref(), true)?,
+                ..Default::default()
+            }),
+        },
+        ..Default::default()
+    };
+    if wfc_settings.path.is_some() && (args.chunked || args.depth.is_some()) {
+        return Err(anyhow!(
+            "--walkable is only supported for single 2D outputs"
+        ));
+    }
+
+    if let Some(depth) = args.depth {
+        return generate_volume(args, tileset, depth, wfc_settings);
+    }
+
+    let (graph, result) = if args.chunked {
+        let mut backend: Box<dyn Backend> = match args.threads {
+            0 | 1 => Box::new(SingleThreaded::new()),
+            threads => Box::new(MultiThreaded::new(threads)),
+        };
+        let generation_mode = match args.deterministic {
+            true => GenerationMode::Deterministic,
+            false => GenerationMode::NonDeterministic,
+        };
+
+        let (world, result) = single_shot::generate_world(
+            tileset.clone(),
+            backend.as_mut(),
+            settings.clone(),
+            args.seed,
+            generation_mode,
+            args.chunk_settings,
+            wfc_settings,
+        );
+
+        let mut graph = grid_graph::create(&settings, WaveFunction::empty());
+        for x in 0..settings.width {
+            for y in 0..settings.height {
+                graph.tiles[y * settings.width + x] = world.world[x][y].clone();
+            }
+        }
+        (graph, result)
+    } else {
+        let filled = WaveFunction::filled(tileset.tile_count());
+        let mut graph = match tileset.as_any().downcast_ref::<OverlappingTileset>() {
+            Some(tileset) => {
+                let mut graph = tileset.create_graph(&settings, filled);
+                tileset.constrain_ground(&mut graph, settings.width);
+                graph
+            }
+            None => grid_graph::create(&settings, filled),
+        };
+        if !settings.periodic {
+            let size = IVec2::new(settings.width as i32, settings.height as i32);
+            let borders = &settings.borders;
+            borders.apply(tileset.as_ref(), &mut graph, IVec2::ZERO, size, size);
+        }
+
+        let mut task = WfcTask {
+            graph,
+            tileset: tileset.clone(),
+            seed: args.seed,
+            metadata: None,
+            settings: wfc_settings,
+            update_channel: None,
+        };
+        let result = SingleThreaded::execute(&mut task);
+        (task.graph, result)
+    };
+
+    // the output is saved even on failure so contradictions can be inspected
+    if args.out.extension().and_then(|ext| ext.to_str()) == Some("json") {
+        std::fs::write(&args.out, to_json(tileset.as_ref(), &graph, args))?;
+    } else if let Some(tileset) = tileset.as_any().downcast_ref::<OverlappingTileset>() {
+        tileset.save_image(&graph, &settings, &args.out)?;
+    } else {
+        render_sprites(tileset.as_ref(), &graph, &settings)?.save(&args.out)?;
+    }
+
+    match result {
+        Ok(()) => {
+            println!("Seed {} saved to {}", args.seed, args.out.display());
+            Ok(())
+        }
+        Err(e) => Err(anyhow!("Failed to generate seed {}: {}", args.seed, e)),
+    }
+}
+
+/// Volumes always use the deterministic schedule of `World3d`, without `--chunked` the whole
+/// volume is a single chunk
+fn generate_volume(
+    args: &Args,
+    tileset: Arc<dyn TileSet>,
+    depth: usize,
+    wfc_settings: WfcSettings,
+) -> Result<()> {
+    if args.out.extension().and_then(|ext| ext.to_str()) != Some("vox") {
+        return Err(anyhow!("volumes can only be saved as .vox"));
+    }
+    if !wfc_settings.counts.is_empty() {
+        return Err(anyhow!("--count is not supported for volumes"));
+    }
+    if !args.borders.is_empty() {
+        return Err(anyhow!(
+            "--border and --outside are not supported for volumes"
+        ));
+    }
+
+    let settings = &args.graph_settings;
+    let size = IVec3::new(settings.width as i32, settings.height as i32, depth as i32);
+    let mut chunk_settings = args.chunk_settings;
+    let mut backend: Box<dyn Backend> = match (args.chunked, args.threads) {
+        (true, 0 | 1) | (false, _) => Box::new(SingleThreaded::new()),
+        (true, threads) => Box::new(MultiThreaded::new(threads)),
+    };
+    if !args.chunked {
+        chunk_settings.size = size.max_element() as usize;
+    }
+
+    let mut world = World3d::new(size, tileset, chunk_settings, wfc_settings, args.seed);
+    let result = world.generate(backend.as_mut());
+
+    // the output is saved even on failure so contradictions can be inspected
+    world.save_vox(&args.out)?;
+
+    match result {
+        Ok(()) => {
+            println!("Seed {} saved to {}", args.seed, args.out.display());
+            Ok(())
+        }
+        Err(e) => Err(anyhow!("Failed to generate seed {}: {}", args.seed, e)),
+    }
+}
+
+/// Rows are written from top to bottom to match the image output, cells that are not collapsed
+/// to a single tile are `null`, `tags` counts the collapsed cells with every tag of the tileset
+fn to_json(tileset: &dyn TileSet, graph: &Graph<WaveFunction>, args: &Args) -> String {
+    let settings = &args.graph_settings;
+    let mut json = String::new();
+    write!(
+        json,
+        "{{\"width\":{},\"height\":{},\"seed\":{},\"tile_count\":{},\"tiles\":[",
+        settings.width,
+        settings.height,
+        args.seed,
+        tileset.tile_count()
+    )
+    .unwrap();
+    for y in (0..settings.height).rev() {
+        json.push('[');
+        for x in 0..settings.width {
+            match graph.tiles[y * settings.width + x].collapse() {
+                Some(tile) => write!(json, "{}", tile).unwrap(),
+                None => json.push_str("null"),
+            }
+            if x + 1 < settings.width {
+                json.push(',');
+            }
+        }
+        json.push(']');
+        if y > 0 {
+            json.push(',');
+        }
+    }
+    json.push_str("],\"tags\":{");
+    for (index, (tag, mask)) in tileset.get_tags().iter().enumerate() {
+        let count = graph
+            .tiles
+            .iter()
+            .filter(|tiles| tiles.collapse().is_some_and(|tile| mask.contains(tile)))
+            .count();
+        if index > 0 {
+            json.push(',');
+        }
+        write!(json, "\"{}\":{}", escape_json(tag), count).unwrap();
+    }
+    json.push_str("}}\n");
+    json
+}
+
+/// Escapes quotes, backslashes and control characters of a json string
+fn escape_json(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Composes the render assets of a tileset into one image, cells that are not collapsed are left
+/// transparent
+fn render_sprites(
+    tileset: &dyn TileSet,
+    graph: &Graph<WaveFunction>,
+    settings: &GridGraphSettings,
+) -> Result<RgbaImage> {
+    let assets = tileset.get_render_tile_assets();
+
+    let mut sprites = Vec::new();
+    for (render, transform) in assets.iter() {
+        sprites.push(match render {
+            TileRender::Sprite(path) => Some(load_sprite(path, transform)?),
+            TileRender::Color(_) => None,
+        });
+    }
+
+    // sprites of big tiles are scaled to cover several cells
+    let tile_size = sprites
+        .iter()
+        .zip(assets.iter())
+        .filter_map(|(sprite, (_, transform))| {
+            let sprite = sprite.as_ref()?;
+            Some((sprite.width() as f32 / transform.scale.x.abs()).round() as u32)
+        })
+        .next()
+        .unwrap_or(1);
+    let sprites = sprites
+        .into_iter()
+        .zip(assets.iter())
+        .map(|(sprite, (render, transform))| match (sprite, render) {
+            (Some(sprite), _) => sprite,
+            (None, TileRender::Color(color)) => {
+                let color = Vec4::from(color.as_rgba_f32()) * 255.0;
+                let pixel = Rgba(color.round().to_array().map(|c| c as u8));
+                let size = transform.scale.truncate().abs() * tile_size as f32;
+                RgbaImage::from_pixel(size.x.round() as u32, size.y.round() as u32, pixel)
+            }
+            (None, TileRender::Sprite(_)) => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut image = RgbaImage::new(
+        settings.width as u32 * tile_size,
+        settings.height as u32 * tile_size,
+    );
+
+    // Note: Assumes that the graph is a grid graph with a standard ordering
+    for y in 0..settings.height {
+        for x in 0..settings.width {
+            if let Some(tile) = graph.tiles[y * settings.width + x].collapse() {
+                let render_tile = tileset.get_render_tile(tile);
+                let (render, transform) = &assets[render_tile];
+                // parts of big tiles that are drawn by another part
+                if matches!(render, TileRender::Color(color) if color.a() == 0.0) {
+                    continue;
+                }
+
+                // the sprite is centered on the cell moved by the translation in cells
+                let sprite = &sprites[render_tile];
+                let center_x = (x as f32 + 0.5 + transform.translation.x) * tile_size as f32;
+                let center_y = (settings.height as f32 - y as f32 - 0.5 - transform.translation.y)
+                    * tile_size as f32;
+                let pos_x = center_x - sprite.width() as f32 / 2.0;
+                let pos_y = center_y - sprite.height() as f32 / 2.0;
+                imageops::replace(
+                    &mut image,
+                    sprite,
+                    pos_x.round() as i64,
+                    pos_y.round() as i64,
+                );
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+/// Sprite paths are either relative to the working directory or to the assets folder
+fn load_sprite(path: &str, transform: &Transform) -> Result<RgbaImage> {
+    let path = match Path::new(path).exists() {
+        true => PathBuf::from(path),
+        false => Path::new("assets").join(path),
+    };
+    let mut sprite = image::open(&path)
+        .map_err(|e| anyhow!("Failed to load {}: {}", path.display(), e))?
+        .to_rgba8();
+
+    // the app scales before rotating so the same order is used here
+    if transform.scale.x < 0.0 {
+        sprite = imageops::flip_horizontal(&sprite);
+    }
+    let turns = (transform.rotation.to_euler(EulerRot::ZYX).0 / FRAC_PI_2).round() as i32;
+    Ok(match turns.rem_euclid(4) {
+        1 => imageops::rotate270(&sprite),
+        2 => imageops::rotate180(&sprite),
+        3 => imageops::rotate90(&sprite),
+        _ => sprite,
+    })
+}
@@ -1,7 +1,11 @@
-use crate::grid_graph::Direction;
+use crate::{
+    big_tile::{add_big_tiles, BigTile},
+    grid_graph::Direction,
+};
+use anyhow::Result;
 use bevy::prelude::*;
 use core_wfc::{TileRender, TileSet, WaveFunction};
-use std::{any::Any, sync::Arc};
+use std::{any::Any, collections::BTreeMap, sync::Arc};
 
 const TILE_COUNT: usize = 120;
 const DIRECTIONS: usize = 4;
@@ -10,6 +14,10 @@ const DIRECTIONS: usize = 4;
 pub struct CarcassonneTileset {
     constraints: Arc<Vec<Vec<WaveFunction>>>,
     weights: Arc<Vec<f32>>,
+    tags: Arc<BTreeMap<String, WaveFunction>>,
+    /// Render assets of the parts of big tiles, which come after the Carcassonne tiles
+    big_tile_render: Vec<(TileRender, Transform)>,
+    needs_neighbor: Arc<Vec<WaveFunction>>,
 }
 
 impl Default for CarcassonneTileset {
@@ -94,16 +102,62 @@ impl Default for CarcassonneTileset {
             weights.push(1.0);
         }
 
+        // tiles are tagged with every edge type they have
+        let mut tags = BTreeMap::new();
+        for (name, edge) in [
+            ("grass", T::Grass),
+            ("road", T::Road),
+            ("city", T::City),
+            ("river", T::River),
+        ] {
+            let mut mask = WaveFunction::empty();
+            for (tile, edges) in rotated_tile_edge_types.iter().enumerate() {
+                if edges.contains(&edge) {
+                    mask.add_tile(tile);
+                }
+            }
+            tags.insert(name.to_string(), mask);
+        }
+
         Self {
             constraints: Arc::new(allowed_neighbors),
             weights: Arc::new(weights),
+            tags: Arc::new(tags),
+            big_tile_render: Vec::new(),
+            needs_neighbor: Arc::default(),
         }
     }
 }
 
+impl CarcassonneTileset {
+    /// The Carcassonne tiles followed by the parts of `big_tiles`, whose edges are `like` the
+    /// Carcassonne tiles
+    pub fn with_big_tiles(big_tiles: &[BigTile]) -> Result<Self> {
+        let mut tileset = Self::default();
+        let mut constraints = tileset.constraints.as_ref().clone();
+        let mut weights = tileset.weights.as_ref().clone();
+        let mut tags = tileset.tags.as_ref().clone();
+        let mut needs_neighbor = Vec::new();
+        add_big_tiles(
+            big_tiles,
+            &mut constraints,
+            &mut weights,
+            &mut tileset.big_tile_render,
+            &mut tags,
+            &mut needs_neighbor,
+        )?;
+
+        tileset.constraints = Arc::new(constraints);
+        tileset.weights = Arc::new(weights);
+        tileset.tags = Arc::new(tags);
+        tileset.needs_neighbor = Arc::new(needs_neighbor);
+        Ok(tileset)
+    }
+}
+
 impl TileSet for CarcassonneTileset {
     fn tile_count(&self) -> usize {
-        TILE_COUNT
+        self.constraints.len()
     }
 
     fn get_constraints(&self) -> Arc<Vec<Vec<WaveFunction>>> {
@@ -120,21 +174,27 @@ impl TileSet for CarcassonneTileset {
 
     fn get_render_tile_assets(&self) -> Vec<(TileRender, Transform)> {
         let mut paths = Vec::new();
-        for tile in 0..self.tile_count() {
+        for tile in 0..TILE_COUNT {
             let transform = Transform::from_rotation(Quat::from_rotation_z(
-                -std::f32::consts::PI / 2.0 * (4 * tile / self.tile_count()) as f32,
+                -std::f32::consts::PI / 2.0 * (4 * tile / TILE_COUNT) as f32,
             ));
             paths.push((
-                TileRender::Sprite(format!(
-                    "carcassonne/{}.png",
-                    tile % (self.tile_count() / 4)
-                )),
+                TileRender::Sprite(format!("carcassonne/{}.png", tile % (TILE_COUNT / 4))),
                 transform,
             ));
         }
+        paths.extend(self.big_tile_render.iter().cloned());
         paths
     }
 
+    fn get_tags(&self) -> Arc<BTreeMap<String, WaveFunction>> {
+        self.tags.clone()
+    }
+
+    fn get_needs_neighbor(&self) -> Arc<Vec<WaveFunction>> {
+        self.needs_neighbor.clone()
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -0,0 +1,162 @@
+use crate::world::ChunkState;
+use anyhow::{anyhow, Result};
+use bevy::prelude::*;
+use core_wfc::{WaveFunction, TILE_U32S};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+const MAGIC: &[u8; 4] = b"HWFC";
+const VERSION: u8 = 1;
+
+/// Everything an `InfiniteWorld` keeps in memory for a single chunk
+#[derive(Clone, Default)]
+pub struct StoredChunk {
+    pub state: Option<ChunkState>,
+    /// Cells of the chunk in row-major order
+    pub cells: Option<Vec<WaveFunction>>,
+    /// Cells generated by the chunk including the overlap around it, see `InfiniteWorld::overlaps`
+    pub overlap: Option<Vec<WaveFunction>>,
+}
+
+/// Storage for chunks that are unloaded from memory
+pub trait ChunkStore: Send + Sync {
+    fn save(&mut self, chunk: IVec2, data: &StoredChunk) -> Result<()>;
+
+    /// Returns `None` if the chunk was never saved
+    fn load(&mut self, chunk: IVec2) -> Result<Option<StoredChunk>>;
+}
+
+/// Stores every chunk as a separate file in a directory
+pub struct FileChunkStore {
+    directory: PathBuf,
+}
+
+impl FileChunkStore {
+    /// Creates the directory if it doesn't exist yet, chunks saved by a previous run are reused
+    pub fn new(directory: &Path) -> Result<Self> {
+        fs::create_dir_all(directory)?;
+        Ok(Self {
+            directory: directory.to_path_buf(),
+        })
+    }
+
+    pub fn chunk_path(&self, chunk: IVec2) -> PathBuf {
+        self.directory
+            .join(format!("{}_{}.chunk", chunk.x, chunk.y))
+    }
+}
+
+impl ChunkStore for FileChunkStore {
+    fn save(&mut self, chunk: IVec2, data: &StoredChunk) -> Result<()> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(match data.state {
+            None => 0,
+            Some(ChunkState::Scheduled) => 1,
+            Some(ChunkState::Done) => 2,
+            Some(ChunkState::Failed) => 3,
+        });
+        write_cells(&mut bytes, data.cells.as_deref());
+        write_cells(&mut bytes, data.overlap.as_deref());
+
+        // write to a temporary file first so a crash never leaves a partially written chunk
+        let path = self.chunk_path(chunk);
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, bytes)?;
+        fs::rename(temporary, path)?;
+
+        Ok(())
+    }
+
+    fn load(&mut self, chunk: IVec2) -> Result<Option<StoredChunk>> {
+        let path = self.chunk_path(chunk);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut reader = Reader {
+            bytes: &bytes,
+            position: 0,
+        };
+        if reader.take(MAGIC.len())? != MAGIC || reader.take(1)?[0] != VERSION {
+            return Err(anyhow!("{:?} is not a chunk file", path));
+        }
+        let state = match reader.take(1)?[0] {
+            0 => None,
+            1 => Some(ChunkState::Scheduled),
+            2 => Some(ChunkState::Done),
+            3 => Some(ChunkState::Failed),
+            state => return Err(anyhow!("Invalid chunk state {} in {:?}", state, path)),
+        };
+        let cells = reader.cells()?;
+        let overlap = reader.cells()?;
+
+        Ok(Some(StoredChunk {
+            state,
+            cells,
+            overlap,
+        }))
+    }
+}
+
+fn write_cells(bytes: &mut Vec<u8>, cells: Option<&[WaveFunction]>) {
+    let cells = match cells {
+        Some(cells) => cells,
+        None => {
+            bytes.push(0);
+            return;
+        }
+    };
+
+    bytes.push(1);
+    bytes.extend_from_slice(&(cells.len() as u32).to_le_bytes());
+    for cell in cells {
+        for value in cell.iter() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + count)
+            .ok_or_else(|| anyhow!("Unexpected end of chunk file"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn cells(&mut self) -> Result<Option<Vec<WaveFunction>>> {
+        if self.take(1)?[0] == 0 {
+            return Ok(None);
+        }
+
+        let count = self.u32()? as usize;
+        let mut cells = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut cell = WaveFunction::empty();
+            for i in 0..TILE_U32S {
+                cell[i] = self.u32()?;
+            }
+            cells.push(cell);
+        }
+        Ok(Some(cells))
+    }
+}
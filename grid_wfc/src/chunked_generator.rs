@@ -0,0 +1,371 @@
+use crate::{
+    grid_graph::Direction,
+    world::{chunk_seed, ChunkState, ChunkType, GenerationMode, World},
+};
+use anyhow::{anyhow, Result};
+use bevy::{prelude::*, utils::HashMap};
+use core_wfc::{
+    wfc_backend::Backend,
+    wfc_task::{InvalidInitialState, Metadata, WfcSettings},
+    WaveFunction, WfcTask,
+};
+use std::sync::Arc;
+
+/// Metadata of every task queued by a `ChunkedGenerator`
+pub struct ChunkTaskData {
+    pub chunk: IVec2,
+    pub chunk_type: ChunkType,
+    /// Discard the chunk was extracted with, needed to merge it back into the world
+    pub discard: usize,
+}
+
+pub struct ChunkResult {
+    pub chunk: IVec2,
+    pub chunk_type: ChunkType,
+    pub result: Result<()>,
+}
+
+pub struct Progress<'a> {
+    pub world: &'a World,
+    pub result: &'a ChunkResult,
+    /// Number of chunks that are done
+    pub finished: usize,
+    pub total: usize,
+}
+
+pub type ProgressCallback = Box<dyn FnMut(Progress) + Send + Sync>;
+
+#[derive(Default)]
+struct Attempts {
+    /// Number of times the chunk was queued, every attempt gets a different seed
+    queued: u32,
+    /// Number of retries since the chunk was last rolled back
+    retries: u32,
+    rollbacks: u32,
+}
+
+/// Schedules the chunks of a `World` on a backend, chunks are queued as soon as the chunks they
+/// depend on are done and failed chunks are recovered according to `ChunkSettings::recovery`
+///
+/// The generator doesn't block, drive it by calling `poll` every frame until `World::outstanding`
+/// is zero or use `run_to_completion`
+#[derive(Default)]
+pub struct ChunkedGenerator {
+    /// Result of every attempt at generating a chunk in the order they finished
+    pub results: Vec<ChunkResult>,
+    failed: bool,
+    progress_callback: Option<ProgressCallback>,
+    attempts: HashMap<IVec2, Attempts>,
+    chunk_types: HashMap<IVec2, ChunkType>,
+    /// Chunks that ran out of retries, rolled back once nothing is generating
+    pending_rollbacks: Vec<IVec2>,
+    /// Rolled back chunks waiting for their neighbors to be generated again
+    waiting: HashMap<IVec2, Vec<IVec2>>,
+}
+
+impl ChunkedGenerator {
+    /// Queues the first chunks of the world, fails if the world is periodic and its chunks can't
+    /// wrap around
+    pub fn start(
+        world: &mut World,
+        backend: &mut dyn Backend,
+        generation_mode: GenerationMode,
+    ) -> Result<Self> {
+        if world.periodic {
+            world.check_periodic(generation_mode)?;
+        }
+        // connecting the walkable tiles of independent chunks would need a path through all of them
+        if world.settings.path.is_some() {
+            return Err(anyhow!(
+                "The path constraint is not supported by chunked generation"
+            ));
+        }
+
+        let mut generator = Self::default();
+        for (chunk, chunk_type) in world.start_generation(generation_mode) {
+            generator.queue_chunk(world, backend, chunk, chunk_type)?;
+        }
+        Ok(generator)
+    }
+
+    /// Called every time a chunk finishes
+    pub fn set_progress_callback(
+        &mut self,
+        callback: impl FnMut(Progress) + Send + Sync + 'static,
+    ) {
+        self.progress_callback = Some(Box::new(callback));
+    }
+
+    /// Returns true once a chunk failed without being able to recover, no new chunks are queued
+    /// after that
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Merges every finished chunk without blocking and queues the chunks that became ready,
+    /// returns the number of chunks that finished
+    pub fn poll(&mut self, world: &mut World, backend: &mut dyn Backend) -> Result<usize> {
+        let mut finished = 0;
+        while let Some((task, result)) = backend.get_output() {
+            self.finish_task(world, backend, task, result)?;
+            finished += 1;
+        }
+        Ok(finished)
+    }
+
+    /// Blocks until every chunk is generated, fails if any of the chunks failed
+    pub fn run_to_completion(
+        &mut self,
+        world: &mut World,
+        backend: &mut dyn Backend,
+    ) -> Result<()> {
+        while world.outstanding > 0 {
+            let (task, result) = backend.wait_for_output();
+            self.finish_task(world, backend, task, result)?;
+        }
+
+        if self.failed {
+            Err(anyhow!("Failed to generate world"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn queue_chunk(
+        &mut self,
+        world: &mut World,
+        backend: &mut dyn Backend,
+        chunk: IVec2,
+        chunk_type: ChunkType,
+    ) -> Result<()> {
+        let attempts = self.attempts.entry(chunk).or_default();
+        let seed = chunk_seed(world.seed, chunk, attempts.queued);
+        let recovery = world.chunk_settings.recovery;
+        let discard =
+            world.chunk_settings.discard + recovery.enlarge_discard * attempts.retries as usize;
+        attempts.queued += 1;
+
+        self.chunk_types.insert(chunk, chunk_type);
+        world.generated_chunks.insert(chunk, ChunkState::Scheduled);
+        let metadata: Metadata = Some(Arc::new(ChunkTaskData {
+            chunk,
+            chunk_type,
+            discard,
+        }));
+        let task = WfcTask {
+            graph: world.extract_chunk(chunk, discard),
+            tileset: world.tileset.clone(),
+            seed,
+            metadata,
+            settings: WfcSettings {
+                counts: world.chunk_counts(chunk, discard),
+                ..world.settings.clone()
+            },
+            update_channel: world.update_channel.as_ref().map(|c| c.0.clone()),
+        };
+
+        world.outstanding += 1;
+        backend.queue_task(task)
+    }
+
+    fn finish_task(
+        &mut self,
+        world: &mut World,
+        backend: &mut dyn Backend,
+        task: WfcTask,
+        result: Result<()>,
+    ) -> Result<()> {
+        world.outstanding -= 1;
+
+        // chunks that were still generating when another chunk failed are ignored
+        if self.failed {
+            return Ok(());
+        }
+
+        let &ChunkTaskData {
+            chunk,
+            chunk_type,
+            discard,
+        } = task
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.downcast_ref())
+            .expect("Task was not queued by a ChunkedGenerator");
+
+        // point at the cell in the world instead of the node in the chunk
+        let result = result.map_err(|e| match e.downcast_ref::<InvalidInitialState>() {
+            Some(&InvalidInitialState { node }) => anyhow!(
+                "Invalid initial state at cell {}",
+                world.chunk_node_position(chunk, discard, node)
+            ),
+            None => e,
+        });
+
+        let mut ready = Vec::new();
+        let mut retry = false;
+        match result {
+            Ok(()) => {
+                world.merge_chunk(chunk, discard, task.graph);
+                world.generated_chunks.insert(chunk, ChunkState::Done);
+                ready = world.process_chunk(chunk, chunk_type);
+
+                // rolled back chunks can continue once all of their neighbors are done again
+                self.waiting.retain(|waiting, neighbors| {
+                    let done = neighbors
+                        .iter()
+                        .all(|n| world.generated_chunks.get(n) == Some(&ChunkState::Done));
+                    if done {
+                        ready.push((*waiting, self.chunk_types[waiting]));
+                    }
+                    !done
+                });
+            }
+            Err(ref e) => {
+                let recovery = world.chunk_settings.recovery;
+                let attempts = self.attempts.entry(chunk).or_default();
+                // failed attempts are only merged once giving up, they would constrain retries
+                if attempts.retries < recovery.retries {
+                    warn!("Retrying chunk {:?}: {:?}", chunk, e);
+                    attempts.retries += 1;
+                    retry = true;
+                } else if attempts.rollbacks < recovery.rollbacks {
+                    warn!("Rolling back the neighbors of chunk {:?}: {:?}", chunk, e);
+                    attempts.retries = 0;
+                    attempts.rollbacks += 1;
+                    world.generated_chunks.insert(chunk, ChunkState::Failed);
+                    self.pending_rollbacks.push(chunk);
+                } else {
+                    error!("Failed to generate chunk {:?}: {:?}", chunk, e);
+                    world.merge_chunk(chunk, discard, task.graph);
+                    world.generated_chunks.insert(chunk, ChunkState::Failed);
+                    self.failed = true;
+                }
+            }
+        }
+
+        self.results.push(ChunkResult {
+            chunk,
+            chunk_type,
+            result,
+        });
+        if let Some(callback) = self.progress_callback.as_mut() {
+            let chunks = world.chunk_count();
+            let finished = world
+                .generated_chunks
+                .values()
+                .filter(|state| **state == ChunkState::Done)
+                .count();
+            callback(Progress {
+                world,
+                result: self.results.last().unwrap(),
+                finished,
+                total: (chunks.x * chunks.y) as usize,
+            });
+        }
+
+        if self.failed {
+            return Ok(());
+        }
+        if retry {
+            self.queue_chunk(world, backend, chunk, chunk_type)?;
+        }
+        for (chunk, chunk_type) in ready {
+            // rolled back neighbors report chunks that are already generated as ready again
+            if !world.generated_chunks.contains_key(&chunk) || self.waiting_done(world, chunk) {
+                self.queue_chunk(world, backend, chunk, chunk_type)?;
+            }
+        }
+        if world.outstanding == 0 {
+            self.rollback(world, backend)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns true for failed chunks that were waiting on their neighbors
+    fn waiting_done(&self, world: &World, chunk: IVec2) -> bool {
+        world.generated_chunks.get(&chunk) == Some(&ChunkState::Failed)
+            && !self.waiting.contains_key(&chunk)
+            && !self.pending_rollbacks.contains(&chunk)
+    }
+
+    /// Clears chunks that ran out of retries along with their finished neighbors and queues the
+    /// neighbors again, the chunks themselves are queued once their neighbors are done
+    fn rollback(&mut self, world: &mut World, backend: &mut dyn Backend) -> Result<()> {
+        for chunk in std::mem::take(&mut self.pending_rollbacks) {
+            let mut neighbors = Vec::new();
+            for direction in 0..4 {
+                let Some(neighbor) =
+                    world.wrap_chunk(chunk + Direction::from(direction).to_ivec2())
+                else {
+                    continue;
+                };
+                // small periodic worlds reach the same neighbor from both sides
+                if neighbors.contains(&neighbor) {
+                    continue;
+                }
+                match world.generated_chunks.get(&neighbor) {
+                    Some(ChunkState::Done) => {
+                        world.clear_chunk(neighbor);
+                        self.queue_chunk(world, backend, neighbor, self.chunk_types[&neighbor])?;
+                        neighbors.push(neighbor);
+                    }
+                    // already being generated again for another rolled back chunk
+                    Some(ChunkState::Scheduled) => neighbors.push(neighbor),
+                    Some(ChunkState::Failed) | None => {}
+                }
+            }
+
+            self.clear_overlap(world, chunk);
+            if neighbors.is_empty() {
+                self.queue_chunk(world, backend, chunk, self.chunk_types[&chunk])?;
+            } else {
+                self.waiting.insert(chunk, neighbors);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Clears the cells of a rolled back chunk that only neighbors being generated again overlap
+    /// with, they would otherwise constrain the neighbors to generate the same borders again
+    fn clear_overlap(&self, world: &mut World, chunk: IVec2) {
+        let overlap = world.chunk_settings.overlap;
+        let mut kept = Vec::new();
+        let mut cleared = Vec::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                let neighbor = chunk + IVec2::new(x, y);
+                if neighbor == chunk {
+                    continue;
+                }
+                // the bounds stay next to the chunk when the neighbor wraps around
+                let bounds = world.chunk_bounds(neighbor, overlap);
+                let state = world
+                    .wrap_chunk(neighbor)
+                    .and_then(|neighbor| world.generated_chunks.get(&neighbor));
+                match state {
+                    Some(ChunkState::Done) => kept.push(bounds),
+                    Some(ChunkState::Scheduled) => cleared.push(bounds),
+                    Some(ChunkState::Failed) | None => {}
+                }
+            }
+        }
+
+        let contains = |(bottom_left, top_right): &(IVec2, IVec2), pos: IVec2| {
+            pos.cmpge(*bottom_left).all() && pos.cmplt(*top_right).all()
+        };
+        let filled = WaveFunction::filled(world.tileset.tile_count());
+        let (bottom_left, top_right) = world.chunk_bounds(chunk, 0);
+        for x in bottom_left.x..top_right.x {
+            for y in bottom_left.y..top_right.y {
+                let pos = IVec2::new(x, y);
+                if cleared.iter().any(|b| contains(b, pos))
+                    && !kept.iter().any(|b| contains(b, pos))
+                {
+                    world.world[x as usize][y as usize] = filled.clone();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use bevy::prelude::*;
+use core_wfc::{Graph, Neighbor};
+
+/// Number of ways a cube can be rotated onto itself
+pub const ROTATIONS: usize = 24;
+
+#[derive(Reflect, Clone)]
+#[reflect(Default)]
+pub struct GridGraph3dSettings {
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+    /// Wraps around separately along every axis
+    pub periodic: BVec3,
+}
+
+impl Default for GridGraph3dSettings {
+    fn default() -> Self {
+        Self {
+            width: 16,
+            height: 16,
+            depth: 16,
+            periodic: BVec3::FALSE,
+        }
+    }
+}
+
+impl GridGraph3dSettings {
+    pub fn size(&self) -> IVec3 {
+        IVec3::new(self.width as i32, self.height as i32, self.depth as i32)
+    }
+
+    /// Nodes are ordered along x first, then y and then z
+    pub fn index(&self, pos: IVec3) -> usize {
+        (pos.z as usize * self.height + pos.y as usize) * self.width + pos.x as usize
+    }
+}
+
+pub fn create<F: Clone>(settings: &GridGraph3dSettings, fill_with: F) -> Graph<F> {
+    let size = settings.size();
+
+    let mut neighbors = Vec::new();
+    for z in 0..size.z {
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let pos = IVec3::new(x, y, z);
+
+                let mut node_neighbors = Vec::new();
+                for direction in 0..6 {
+                    let mut neighbor_pos = pos + Direction3d::from(direction).to_ivec3();
+                    let outside = neighbor_pos.cmplt(IVec3::ZERO) | neighbor_pos.cmpge(size);
+                    if (outside & !settings.periodic).any() {
+                        continue;
+                    }
+                    neighbor_pos = IVec3::new(
+                        neighbor_pos.x.rem_euclid(size.x),
+                        neighbor_pos.y.rem_euclid(size.y),
+                        neighbor_pos.z.rem_euclid(size.z),
+                    );
+
+                    node_neighbors.push(Neighbor {
+                        direction,
+                        index: settings.index(neighbor_pos),
+                    });
+                }
+                neighbors.push(node_neighbors);
+            }
+        }
+    }
+
+    let tiles = vec![fill_with; settings.width * settings.height * settings.depth];
+
+    Graph { tiles, neighbors }
+}
+
+/// Splits a volume into cubes of `chunk_size` for `core_wfc::graph_chunker`
+pub fn partition(settings: &GridGraph3dSettings, chunk_size: usize) -> Vec<usize> {
+    let chunks_x = settings.width.div_ceil(chunk_size);
+    let chunks_y = settings.height.div_ceil(chunk_size);
+
+    let mut partition = Vec::with_capacity(settings.width * settings.height * settings.depth);
+    for z in 0..settings.depth {
+        for y in 0..settings.height {
+            for x in 0..settings.width {
+                let chunk =
+                    (z / chunk_size * chunks_y + y / chunk_size) * chunks_x + x / chunk_size;
+                partition.push(chunk);
+            }
+        }
+    }
+    partition
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
+pub enum Direction3d {
+    Right = 0,
+    Left = 1,
+    Up = 2,
+    Down = 3,
+    Forward = 4,
+    Back = 5,
+}
+
+impl Direction3d {
+    pub fn other(&self) -> Self {
+        Self::from(*self as usize ^ 1)
+    }
+
+    /// Applies one of the `ROTATIONS` rotations of a cube, rotation 0 leaves the direction as is
+    pub fn rotate(&self, rotation: usize) -> Self {
+        let [x, y, z] = rotation_axes(rotation);
+        let v = self.to_ivec3();
+        Self::from_ivec3(x * v.x + y * v.y + z * v.z)
+    }
+
+    pub fn to_ivec3(&self) -> IVec3 {
+        match self {
+            Self::Right => IVec3::X,
+            Self::Left => IVec3::NEG_X,
+            Self::Up => IVec3::Y,
+            Self::Down => IVec3::NEG_Y,
+            Self::Forward => IVec3::Z,
+            Self::Back => IVec3::NEG_Z,
+        }
+    }
+
+    pub fn from_ivec3(value: IVec3) -> Self {
+        match value.to_array() {
+            [1, 0, 0] => Self::Right,
+            [-1, 0, 0] => Self::Left,
+            [0, 1, 0] => Self::Up,
+            [0, -1, 0] => Self::Down,
+            [0, 0, 1] => Self::Forward,
+            [0, 0, -1] => Self::Back,
+            _ => panic!("Invalid direction: {:?}", value),
+        }
+    }
+}
+
+impl From<usize> for Direction3d {
+    fn from(value: usize) -> Self {
+        match value {
+            0 => Self::Right,
+            1 => Self::Left,
+            2 => Self::Up,
+            3 => Self::Down,
+            4 => Self::Forward,
+            5 => Self::Back,
+            _ => panic!("Invalid direction: {}", value),
+        }
+    }
+}
+
+/// Where the x, y and z axes end up after a rotation, the rotations are all permutations of the
+/// axes with signs that don't mirror the cube
+fn rotation_axes(rotation: usize) -> [IVec3; 3] {
+    let axes = [IVec3::X, IVec3::Y, IVec3::Z];
+    let permutations = [
+        [0, 1, 2],
+        [1, 2, 0],
+        [2, 0, 1],
+        [0, 2, 1],
+        [2, 1, 0],
+        [1, 0, 2],
+    ];
+
+    let mut rotations = Vec::with_capacity(ROTATIONS);
+    for (i, permutation) in permutations.iter().enumerate() {
+        for signs in 0..8 {
+            let sign = |axis: usize| if signs & (1 << axis) == 0 { 1 } else { -1 };
+            let flips = (0..3).filter(|axis| sign(*axis) == -1).count();
+            // odd permutations need an odd number of flips to stay a rotation
+            if (flips + i / 3) % 2 == 0 {
+                rotations.push([0, 1, 2].map(|axis| axes[permutation[axis]] * sign(axis)));
+            }
+        }
+    }
+    rotations[rotation]
+}
@@ -1,5 +1,5 @@
 use bevy::prelude::*;
-use core_wfc::{Graph, Neighbor};
+use core_wfc::{Graph, Neighbor, TileSet, WaveFunction};
 
 #[derive(Reflect, Clone)]
 #[reflect(Default)]
@@ -7,6 +7,11 @@ pub struct GridGraphSettings {
     pub width: usize,
     pub height: usize,
     pub periodic: bool,
+    /// Connects cells diagonally as well, tilesets without constraints for the diagonal directions
+    /// leave them unconstrained
+    pub diagonals: bool,
+    /// Rules for the cells along the edges, only used when the grid is not periodic
+    pub borders: Borders,
 }
 
 impl Default for GridGraphSettings {
@@ -15,6 +20,108 @@ impl Default for GridGraphSettings {
             width: 64,
             height: 64,
             periodic: false,
+            diagonals: false,
+            borders: Borders::default(),
+        }
+    }
+}
+
+/// Rule for the cells along one edge of a grid
+#[derive(Reflect, Clone, Default, PartialEq, Debug)]
+#[reflect(Default)]
+pub enum Border {
+    /// Cells along the edge are unconstrained
+    #[default]
+    Free,
+    /// Cells along the edge are restricted to these tiles, like ocean around an island
+    Tiles(Vec<usize>),
+    /// Cells outside of the edge act as one of these tiles, so the cells along the edge have to
+    /// be allowed next to one of them
+    Outside(Vec<usize>),
+}
+
+impl Border {
+    /// Tiles allowed along the edge facing `direction`, `None` if the edge is free
+    pub fn mask(&self, tileset: &dyn TileSet, direction: Direction) -> Option<WaveFunction> {
+        let mut mask = WaveFunction::empty();
+        match self {
+            Border::Free => return None,
+            Border::Tiles(tiles) => {
+                for &tile in tiles.iter() {
+                    mask.add_tile(tile);
+                }
+            }
+            Border::Outside(tiles) => {
+                let constraints = tileset.get_constraints();
+                for &tile in tiles.iter() {
+                    let allowed = &constraints[tile][direction.other() as usize];
+                    mask = WaveFunction::join(&mask, allowed);
+                }
+            }
+        }
+        Some(mask)
+    }
+}
+
+#[derive(Reflect, Clone, Default, PartialEq, Debug)]
+#[reflect(Default)]
+pub struct Borders {
+    pub left: Border,
+    pub right: Border,
+    pub bottom: Border,
+    pub top: Border,
+}
+
+impl Borders {
+    pub const FREE: Self = Self {
+        left: Border::Free,
+        right: Border::Free,
+        bottom: Border::Free,
+        top: Border::Free,
+    };
+
+    /// Same rule on every edge
+    pub fn all(border: Border) -> Self {
+        Self {
+            left: border.clone(),
+            right: border.clone(),
+            bottom: border.clone(),
+            top: border,
+        }
+    }
+
+    /// Restricts the cells of a grid graph covering `size` cells from `bottom_left` that lie
+    /// along the edges of a grid of `grid_size` cells
+    pub fn apply(
+        &self,
+        tileset: &dyn TileSet,
+        graph: &mut Graph<WaveFunction>,
+        bottom_left: IVec2,
+        size: IVec2,
+        grid_size: IVec2,
+    ) {
+        let edges = [
+            (&self.left, Direction::Left),
+            (&self.right, Direction::Right),
+            (&self.bottom, Direction::Down),
+            (&self.top, Direction::Up),
+        ];
+        for (border, direction) in edges {
+            let Some(mask) = border.mask(tileset, direction) else {
+                continue;
+            };
+
+            // Note: Assumes that the graph is a grid graph with a standard ordering
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let outside = bottom_left + IVec2::new(x, y) + direction.to_ivec2();
+                    if outside.cmpge(IVec2::ZERO).all() && outside.cmplt(grid_size).all() {
+                        continue;
+                    }
+                    let index = y as usize * size.x as usize + x as usize;
+                    graph.tiles[index] = WaveFunction::intersect(&graph.tiles[index], &mask);
+                }
+            }
         }
     }
 }
@@ -29,12 +136,10 @@ pub fn create<F: Clone>(settings: &GridGraphSettings, fill_with: F) -> Graph<F>
         }
     }
 
-    let directions = [
-        IVec2::new(0, 1),
-        IVec2::new(0, -1),
-        IVec2::new(-1, 0),
-        IVec2::new(1, 0),
-    ];
+    let direction_count = if settings.diagonals { 8 } else { 4 };
+    let directions: Vec<_> = (0..direction_count)
+        .map(|direction| Direction::from(direction).to_ivec2())
+        .collect();
 
     let mut neighbors = Vec::new();
     for pos in nodes_pos.iter() {
@@ -68,47 +173,44 @@ pub fn create<F: Clone>(settings: &GridGraphSettings, fill_with: F) -> Graph<F>
     Graph { tiles, neighbors }
 }
 
+/// Splits a grid graph into square clusters of `chunk_size` for `core_wfc::graph_chunker`
+pub fn partition(settings: &GridGraphSettings, chunk_size: usize) -> Vec<usize> {
+    let chunks_x = settings.width.div_ceil(chunk_size);
+
+    // Note: Assumes that the graph is a grid graph with a standard ordering
+    let mut partition = Vec::with_capacity(settings.width * settings.height);
+    for y in 0..settings.height {
+        for x in 0..settings.width {
+            partition.push(y / chunk_size * chunks_x + x / chunk_size);
+        }
+    }
+    partition
+}
+
 #[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
 pub enum Direction {
     Up = 0,
     Down = 1,
     Left = 2,
     Right = 3,
+    UpLeft = 4,
+    UpRight = 5,
+    DownLeft = 6,
+    DownRight = 7,
 }
 
 impl Direction {
     pub fn other(&self) -> Self {
-        match self {
-            Self::Up => Self::Down,
-            Self::Down => Self::Up,
-            Self::Left => Self::Right,
-            Self::Right => Self::Left,
-        }
+        Self::from_ivec2(-self.to_ivec2())
     }
 
+    /// Rotates clockwise in steps of 90 degrees
     pub fn rotate(&self, rotation: usize) -> Self {
-        match rotation {
-            0 => *self,
-            1 => match self {
-                Self::Up => Self::Right,
-                Self::Down => Self::Left,
-                Self::Left => Self::Up,
-                Self::Right => Self::Down,
-            },
-            2 => match self {
-                Self::Up => Self::Down,
-                Self::Down => Self::Up,
-                Self::Left => Self::Right,
-                Self::Right => Self::Left,
-            },
-            3 => match self {
-                Self::Up => Self::Left,
-                Self::Down => Self::Right,
-                Self::Left => Self::Down,
-                Self::Right => Self::Up,
-            },
-            _ => panic!("Invalid rotation: {}", rotation),
+        let mut vec = self.to_ivec2();
+        for _ in 0..rotation % 4 {
+            vec = IVec2::new(vec.y, -vec.x);
         }
+        Self::from_ivec2(vec)
     }
 
     pub fn to_ivec2(&self) -> IVec2 {
@@ -117,6 +219,24 @@ impl Direction {
             Self::Down => IVec2::new(0, -1),
             Self::Left => IVec2::new(-1, 0),
             Self::Right => IVec2::new(1, 0),
+            Self::UpLeft => IVec2::new(-1, 1),
+            Self::UpRight => IVec2::new(1, 1),
+            Self::DownLeft => IVec2::new(-1, -1),
+            Self::DownRight => IVec2::new(1, -1),
+        }
+    }
+
+    pub fn from_ivec2(value: IVec2) -> Self {
+        match value.to_array() {
+            [0, 1] => Self::Up,
+            [0, -1] => Self::Down,
+            [-1, 0] => Self::Left,
+            [1, 0] => Self::Right,
+            [-1, 1] => Self::UpLeft,
+            [1, 1] => Self::UpRight,
+            [-1, -1] => Self::DownLeft,
+            [1, -1] => Self::DownRight,
+            _ => panic!("Invalid direction: {:?}", value),
         }
     }
 }
@@ -128,6 +248,10 @@ impl From<usize> for Direction {
             1 => Self::Down,
             2 => Self::Left,
             3 => Self::Right,
+            4 => Self::UpLeft,
+            5 => Self::UpRight,
+            6 => Self::DownLeft,
+            7 => Self::DownRight,
             _ => panic!("Invalid direction: {}", value),
         }
     }
@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+use core_wfc::{Graph, Neighbor};
+
+/// Pointy top hexagons in "odd-r" offset coordinates, odd rows are shifted half a hex to the
+/// right and nodes are stored row by row like `grid_graph`
+#[derive(Reflect, Clone)]
+#[reflect(Default)]
+pub struct HexGraphSettings {
+    pub width: usize,
+    /// Has to be even for periodic graphs so the rows line up when wrapping around
+    pub height: usize,
+    pub periodic: bool,
+}
+
+impl Default for HexGraphSettings {
+    fn default() -> Self {
+        Self {
+            width: 32,
+            height: 32,
+            periodic: false,
+        }
+    }
+}
+
+pub fn create<F: Clone>(settings: &HexGraphSettings, fill_with: F) -> Graph<F> {
+    assert!(
+        !settings.periodic || settings.height & 1 == 0,
+        "Periodic hex graphs need an even height"
+    );
+    let size = IVec2::new(settings.width as i32, settings.height as i32);
+
+    let mut neighbors = Vec::new();
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let axial = offset_to_axial(IVec2::new(x, y));
+
+            let mut node_neighbors = Vec::new();
+            for direction in 0..6 {
+                let mut neighbor_pos =
+                    axial_to_offset(axial + HexDirection::from(direction).to_axial());
+                if settings.periodic {
+                    neighbor_pos = IVec2::new(
+                        neighbor_pos.x.rem_euclid(size.x),
+                        neighbor_pos.y.rem_euclid(size.y),
+                    );
+                } else if neighbor_pos.cmplt(IVec2::ZERO).any() || neighbor_pos.cmpge(size).any() {
+                    continue;
+                }
+
+                node_neighbors.push(Neighbor {
+                    direction,
+                    index: (neighbor_pos.y * size.x + neighbor_pos.x) as usize,
+                });
+            }
+            neighbors.push(node_neighbors);
+        }
+    }
+
+    let tiles = vec![fill_with; settings.width * settings.height];
+
+    Graph { tiles, neighbors }
+}
+
+/// Splits a hex graph into clusters of `chunk_size` by `chunk_size` offset coordinates for
+/// `core_wfc::graph_chunker`
+pub fn partition(settings: &HexGraphSettings, chunk_size: usize) -> Vec<usize> {
+    let chunks_x = settings.width.div_ceil(chunk_size);
+
+    let mut partition = Vec::with_capacity(settings.width * settings.height);
+    for y in 0..settings.height {
+        for x in 0..settings.width {
+            partition.push(y / chunk_size * chunks_x + x / chunk_size);
+        }
+    }
+    partition
+}
+
+/// Converts "odd-r" offset coordinates to axial coordinates, the second axis goes up and to the
+/// right
+pub fn offset_to_axial(offset: IVec2) -> IVec2 {
+    IVec2::new(offset.x - (offset.y - (offset.y & 1)) / 2, offset.y)
+}
+
+pub fn axial_to_offset(axial: IVec2) -> IVec2 {
+    IVec2::new(axial.x + (axial.y - (axial.y & 1)) / 2, axial.y)
+}
+
+/// Center of a hex in world space for hexes with a distance of 1 between neighbors
+pub fn offset_to_position(offset: IVec2) -> Vec2 {
+    Vec2::new(
+        offset.x as f32 + 0.5 * (offset.y & 1) as f32,
+        offset.y as f32 * 3f32.sqrt() / 2.0,
+    )
+}
+
+/// Directions in counter clockwise order starting to the right
+#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
+pub enum HexDirection {
+    Right = 0,
+    UpRight = 1,
+    UpLeft = 2,
+    Left = 3,
+    DownLeft = 4,
+    DownRight = 5,
+}
+
+impl HexDirection {
+    pub fn other(&self) -> Self {
+        self.rotate(3)
+    }
+
+    /// Rotates counter clockwise in steps of 60 degrees
+    pub fn rotate(&self, rotation: usize) -> Self {
+        Self::from((*self as usize + rotation) % 6)
+    }
+
+    pub fn to_axial(&self) -> IVec2 {
+        match self {
+            Self::Right => IVec2::new(1, 0),
+            Self::UpRight => IVec2::new(0, 1),
+            Self::UpLeft => IVec2::new(-1, 1),
+            Self::Left => IVec2::new(-1, 0),
+            Self::DownLeft => IVec2::new(0, -1),
+            Self::DownRight => IVec2::new(1, -1),
+        }
+    }
+}
+
+impl From<usize> for HexDirection {
+    fn from(value: usize) -> Self {
+        match value {
+            0 => Self::Right,
+            1 => Self::UpRight,
+            2 => Self::UpLeft,
+            3 => Self::Left,
+            4 => Self::DownLeft,
+            5 => Self::DownRight,
+            _ => panic!("Invalid direction: {}", value),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::hex_graph::HexDirection;
+use bevy::prelude::*;
+use core_wfc::{TileRender, TileSet, WaveFunction};
+use std::{any::Any, sync::Arc};
+
+const DIRECTIONS: usize = 6;
+
+/// A tile with a socket on each side in the order of `HexDirection`, tiles fit next to each other
+/// if the sockets facing each other are equal
+#[derive(Debug, Clone)]
+pub struct HexTile {
+    pub sockets: [usize; DIRECTIONS],
+    pub weight: f32,
+    pub color: Color,
+}
+
+#[derive(Debug, Clone)]
+pub struct HexTileset {
+    /// Index of the original tile and the rotation of every tile
+    tiles: Vec<(usize, usize)>,
+    colors: Vec<Color>,
+    constraints: Arc<Vec<Vec<WaveFunction>>>,
+    weights: Arc<Vec<f32>>,
+}
+
+impl HexTileset {
+    /// Adds all 6 rotations of every tile, rotations that look the same as a previous one are
+    /// skipped
+    pub fn new(tiles: &[HexTile]) -> Self {
+        let mut rotated_tiles = Vec::new();
+        let mut rotated_sockets: Vec<[usize; DIRECTIONS]> = Vec::new();
+        for (index, tile) in tiles.iter().enumerate() {
+            let first = rotated_sockets.len();
+            for rotation in 0..DIRECTIONS {
+                let mut sockets = [0; DIRECTIONS];
+                for (direction, socket) in tile.sockets.iter().enumerate() {
+                    sockets[HexDirection::from(direction).rotate(rotation) as usize] = *socket;
+                }
+                if !rotated_sockets[first..].contains(&sockets) {
+                    rotated_tiles.push((index, rotation));
+                    rotated_sockets.push(sockets);
+                }
+            }
+        }
+
+        // convert to allowed neighbors
+        let mut allowed_neighbors = Vec::with_capacity(rotated_sockets.len());
+        for sockets in rotated_sockets.iter() {
+            let mut allowed_neighbors_for_tile = Vec::with_capacity(DIRECTIONS);
+            for (direction, socket) in sockets.iter().enumerate() {
+                let other_direction = HexDirection::from(direction).other() as usize;
+                let mut cell = WaveFunction::empty();
+                for (other_tile, other_sockets) in rotated_sockets.iter().enumerate() {
+                    if other_sockets[other_direction] == *socket {
+                        cell.add_tile(other_tile);
+                    }
+                }
+                allowed_neighbors_for_tile.push(cell);
+            }
+            allowed_neighbors.push(allowed_neighbors_for_tile);
+        }
+
+        // the weight of a tile is split between its rotations
+        let weights = rotated_tiles
+            .iter()
+            .map(|(index, _)| {
+                let rotations = rotated_tiles.iter().filter(|(i, _)| i == index).count();
+                tiles[*index].weight / rotations as f32
+            })
+            .collect();
+
+        Self {
+            colors: tiles.iter().map(|tile| tile.color).collect(),
+            tiles: rotated_tiles,
+            constraints: Arc::new(allowed_neighbors),
+            weights: Arc::new(weights),
+        }
+    }
+
+    /// Returns the index of the tile passed to `new` and its rotation
+    pub fn get_tile(&self, tile: usize) -> (usize, usize) {
+        self.tiles[tile]
+    }
+}
+
+impl Default for HexTileset {
+    /// Grass and water with roads and coasts in between
+    fn default() -> Self {
+        #[derive(Clone, Copy)]
+        enum Socket {
+            Grass,
+            Water,
+            Road,
+            Shore,
+        }
+        use Socket::*;
+
+        let tile = |sockets: [Socket; DIRECTIONS], weight, color| HexTile {
+            sockets: sockets.map(|socket| socket as usize),
+            weight,
+            color,
+        };
+        let grass = Color::rgb(0.3, 0.6, 0.2);
+        let water = Color::rgb(0.2, 0.4, 0.8);
+        let road = Color::rgb(0.6, 0.5, 0.3);
+        let shore = Color::rgb(0.8, 0.75, 0.5);
+
+        Self::new(&[
+            tile([Grass; DIRECTIONS], 6.0, grass),
+            tile([Water; DIRECTIONS], 4.0, water),
+            tile([Road, Grass, Grass, Road, Grass, Grass], 1.0, road),
+            tile([Road, Grass, Road, Grass, Grass, Grass], 1.0, road),
+            tile([Road, Grass, Road, Grass, Road, Grass], 0.3, road),
+            tile([Road, Grass, Grass, Grass, Grass, Grass], 0.2, road),
+            tile([Water, Water, Shore, Grass, Grass, Shore], 1.0, shore),
+            tile([Water, Shore, Grass, Grass, Grass, Shore], 0.5, shore),
+            tile([Water, Water, Water, Shore, Grass, Shore], 0.5, shore),
+        ])
+    }
+}
+
+impl TileSet for HexTileset {
+    fn tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+
+    fn get_constraints(&self) -> Arc<Vec<Vec<WaveFunction>>> {
+        self.constraints.clone()
+    }
+
+    fn get_weights(&self) -> Arc<Vec<f32>> {
+        self.weights.clone()
+    }
+
+    fn set_weights(&mut self, weights: Vec<f32>) {
+        self.weights = Arc::new(weights);
+    }
+
+    fn get_render_tile_assets(&self) -> Vec<(TileRender, Transform)> {
+        self.tiles
+            .iter()
+            .map(|(index, rotation)| {
+                let transform = Transform::from_rotation(Quat::from_rotation_z(
+                    std::f32::consts::PI / 3.0 * *rotation as f32,
+                ));
+                (TileRender::Color(self.colors[*index]), transform)
+            })
+            .collect()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
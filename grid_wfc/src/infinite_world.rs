@@ -0,0 +1,600 @@
+use crate::{
+    chunk_store::{ChunkStore, StoredChunk},
+    grid_graph::{self, Borders, Direction, GridGraphSettings},
+    overlapping_tileset::OverlappingTileset,
+    world::{self, ChunkSettings, ChunkState, ChunkType, GenerationMode},
+};
+use anyhow::{anyhow, Result};
+use bevy::{
+    math::IRect,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use core_wfc::{
+    wfc_backend::Backend,
+    wfc_task::{Metadata, WfcSettings},
+    Graph, TileSet, WaveFunction, WfcTask,
+};
+use std::{collections::VecDeque, sync::Arc};
+
+/// Unbounded variant of `World` where cells are stored sparsely per chunk and chunks, including
+/// ones at negative coordinates, are generated on demand
+///
+/// With `GenerationMode::Deterministic` the role and seed of every chunk are derived from its
+/// coordinates and chunks only read the generated cells of the chunks they depend on, so the same
+/// seed gives the same tiles no matter which regions are requested and in which order
+///
+/// Chunks can be unloaded into a `ChunkStore` to keep memory bounded, they are loaded again
+/// automatically when a chunk next to them is generated
+#[derive(Resource)]
+pub struct InfiniteWorld {
+    /// Cells of each chunk in row-major order, chunks without an entry are fully unconstrained
+    pub chunks: HashMap<IVec2, Vec<WaveFunction>>,
+    /// Cells generated by each chunk including the overlap around it in row-major order, only
+    /// kept with `GenerationMode::Deterministic` where chunks read the overlap of their dependencies
+    pub overlaps: HashMap<IVec2, Vec<WaveFunction>>,
+    pub generated_chunks: HashMap<IVec2, ChunkState>,
+    /// Number of times each chunk failed, a failed chunk is retried with the seed of its next
+    /// attempt
+    pub attempts: HashMap<IVec2, u32>,
+    /// Requested chunks waiting for a neighbor to finish generating
+    pub requested: VecDeque<IVec2>,
+    /// First generated chunk, generation grows outwards from here
+    pub origin: Option<IVec2>,
+    pub chunk_settings: ChunkSettings,
+    pub tileset: Arc<dyn TileSet>,
+    pub generation_mode: GenerationMode,
+    pub seed: u64,
+    pub outstanding: usize,
+    pub settings: WfcSettings,
+    /// Where unloaded chunks are saved, chunks can't be unloaded without a store
+    pub store: Option<Box<dyn ChunkStore>>,
+    /// Chunks the store has nothing for, so waiting chunks don't look up their neighbors again
+    /// every time they are polled. Assumes nothing else saves to the store
+    pub missing: HashSet<IVec2>,
+}
+
+impl InfiniteWorld {
+    pub fn new(
+        tileset: Arc<dyn TileSet>,
+        chunk_settings: ChunkSettings,
+        settings: WfcSettings,
+        seed: u64,
+        generation_mode: GenerationMode,
+    ) -> Self {
+        Self {
+            chunks: HashMap::new(),
+            overlaps: HashMap::new(),
+            generated_chunks: HashMap::new(),
+            attempts: HashMap::new(),
+            requested: VecDeque::new(),
+            origin: None,
+            chunk_settings,
+            tileset,
+            generation_mode,
+            seed,
+            outstanding: 0,
+            settings,
+            store: None,
+            missing: HashSet::new(),
+        }
+    }
+
+    /// Chunk containing a cell and the index of the cell within that chunk
+    pub fn cell_location(&self, pos: IVec2) -> (IVec2, usize) {
+        let size = self.chunk_settings.size as i32;
+        let chunk = pos.div_euclid(IVec2::splat(size));
+        let local = pos.rem_euclid(IVec2::splat(size));
+        (chunk, (local.y * size + local.x) as usize)
+    }
+
+    pub fn get_cell(&self, pos: IVec2) -> WaveFunction {
+        let (chunk, index) = self.cell_location(pos);
+        match self.chunks.get(&chunk) {
+            Some(cells) => cells[index].clone(),
+            None => WaveFunction::filled(self.tileset.tile_count()),
+        }
+    }
+
+    /// Chunks that were unloaded have to be loaded with `load_chunk` first, otherwise the stored
+    /// chunk is ignored
+    pub fn set_cell(&mut self, pos: IVec2, tile: WaveFunction) {
+        let (chunk, index) = self.cell_location(pos);
+        let cell_count = self.chunk_settings.size * self.chunk_settings.size;
+        let filled = WaveFunction::filled(self.tileset.tile_count());
+        self.chunks
+            .entry(chunk)
+            .or_insert_with(|| vec![filled; cell_count])[index] = tile;
+    }
+
+    /// Same as `World::chunk_bounds` without clamping to the size of the world
+    pub fn chunk_bounds(&self, pos: IVec2, overlap: usize) -> (IVec2, IVec2) {
+        let size = self.chunk_settings.size as i32;
+        let bottom_left = pos * size - IVec2::splat(overlap as i32);
+        let top_right = (pos + IVec2::ONE) * size + IVec2::splat(overlap as i32);
+        (bottom_left, top_right)
+    }
+
+    pub fn extract_chunk(&self, chunk: IVec2) -> Graph<WaveFunction> {
+        let (bottom_left, top_right) = self.chunk_bounds(
+            chunk,
+            self.chunk_settings.overlap + self.chunk_settings.discard,
+        );
+        let size = top_right - bottom_left;
+
+        let settings = GridGraphSettings {
+            width: size.x as usize,
+            height: size.y as usize,
+            periodic: false,
+            diagonals: false,
+            borders: Borders::default(),
+        };
+        let filled = WaveFunction::filled(self.tileset.tile_count());
+        let mut graph = match self.tileset.as_any().downcast_ref::<OverlappingTileset>() {
+            Some(tileset) => tileset.create_graph(&settings, filled),
+            None => grid_graph::create(&settings, filled),
+        };
+
+        let (chunk_bottom_left, chunk_top_right) = self.chunk_bounds(chunk, 0);
+        let merging = self.chunk_settings.merging;
+        let dependencies = ChunkType::from_coordinates(chunk).dependencies(chunk);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let pos = bottom_left + IVec2::new(x, y);
+                let inside = pos.cmpge(chunk_bottom_left).all() && pos.cmplt(chunk_top_right).all();
+                let tile = match self.generation_mode {
+                    GenerationMode::NonDeterministic if merging.should_extract(inside) => {
+                        Some(self.get_cell(pos))
+                    }
+                    GenerationMode::Deterministic if !inside => {
+                        self.dependency_cell(&dependencies, pos)
+                    }
+                    _ => None,
+                };
+                if let Some(tile) = tile {
+                    graph.tiles[(y * size.x + x) as usize] = tile;
+                }
+            }
+        }
+
+        graph
+    }
+
+    /// Cell as generated by the dependencies of a chunk, cells of a dependency are used as is and
+    /// cells in the overlap of multiple dependencies are intersected so the result doesn't depend
+    /// on generation order
+    fn dependency_cell(&self, dependencies: &[IVec2], pos: IVec2) -> Option<WaveFunction> {
+        let (owner, _) = self.cell_location(pos);
+        if dependencies.contains(&owner)
+            && self.generated_chunks.get(&owner) == Some(&ChunkState::Done)
+        {
+            return Some(self.get_cell(pos));
+        }
+
+        let mut cell: Option<WaveFunction> = None;
+        let width = self.chunk_settings.size + self.chunk_settings.overlap * 2;
+        for dependency in dependencies {
+            let overlap = match self.overlaps.get(dependency) {
+                Some(overlap) => overlap,
+                None => continue,
+            };
+            let (bottom_left, top_right) =
+                self.chunk_bounds(*dependency, self.chunk_settings.overlap);
+            if pos.cmplt(bottom_left).any() || pos.cmpge(top_right).any() {
+                continue;
+            }
+
+            let local = pos - bottom_left;
+            let tile = &overlap[local.y as usize * width + local.x as usize];
+            cell = Some(match cell {
+                Some(cell) => WaveFunction::intersect(&cell, tile),
+                None => tile.clone(),
+            });
+        }
+        cell
+    }
+
+    pub fn merge_chunk(&mut self, chunk: IVec2, graph: Graph<WaveFunction>) {
+        let (bottom_left, top_right) = self.chunk_bounds(
+            chunk,
+            self.chunk_settings.overlap + self.chunk_settings.discard,
+        );
+        let size = top_right - bottom_left;
+
+        let (chunk_bottom_left, chunk_top_right) = self.chunk_bounds(chunk, 0);
+        let (discard_bottom_left, discard_top_right) =
+            self.chunk_bounds(chunk, self.chunk_settings.overlap);
+        let merging = self.chunk_settings.merging;
+        let mut overlap = Vec::new();
+
+        // Note: Assumes that the graph is a grid graph with a standard ordering
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let pos = bottom_left + IVec2::new(x, y);
+                if pos.cmplt(discard_bottom_left).any() || pos.cmpge(discard_top_right).any() {
+                    continue;
+                }
+
+                let tile = &graph.tiles[(y * size.x + x) as usize];
+                let inside = pos.cmpge(chunk_bottom_left).all() && pos.cmplt(chunk_top_right).all();
+                let current = self.get_cell(pos);
+                let merge = match self.generation_mode {
+                    GenerationMode::NonDeterministic => {
+                        merging.should_merge(inside, &current, tile)
+                    }
+                    // the overlap is kept separately, writing it into the world would make the
+                    // cells seen by other chunks depend on generation order
+                    GenerationMode::Deterministic => inside,
+                };
+                if merge {
+                    self.set_cell(pos, tile.clone());
+                }
+                overlap.push(tile.clone());
+            }
+        }
+
+        if self.generation_mode == GenerationMode::Deterministic {
+            self.overlaps.insert(chunk, overlap);
+        }
+    }
+
+    /// Number of chunks in each direction that share cells with a chunk
+    fn reach(&self) -> i32 {
+        let padding = self.chunk_settings.overlap + self.chunk_settings.discard;
+        padding.div_ceil(self.chunk_settings.size) as i32
+    }
+
+    /// Returns true if anything of the chunk is in memory
+    pub fn is_loaded(&self, chunk: IVec2) -> bool {
+        self.chunks.contains_key(&chunk)
+            || self.overlaps.contains_key(&chunk)
+            || self.generated_chunks.contains_key(&chunk)
+    }
+
+    /// Loads a chunk from the store if it isn't in memory yet, returns false if there is nothing
+    /// to load
+    pub fn load_chunk(&mut self, chunk: IVec2) -> Result<bool> {
+        if self.is_loaded(chunk) {
+            return Ok(true);
+        }
+        if self.missing.contains(&chunk) {
+            return Ok(false);
+        }
+        let stored = match self.store.as_mut() {
+            Some(store) => store.load(chunk)?,
+            None => None,
+        };
+        let stored = match stored {
+            Some(stored) => stored,
+            None => {
+                if self.store.is_some() {
+                    self.missing.insert(chunk);
+                }
+                return Ok(false);
+            }
+        };
+
+        if let Some(state) = stored.state {
+            self.generated_chunks.insert(chunk, state);
+        }
+        if let Some(cells) = stored.cells {
+            self.chunks.insert(chunk, cells);
+        }
+        if let Some(overlap) = stored.overlap {
+            self.overlaps.insert(chunk, overlap);
+        }
+
+        Ok(true)
+    }
+
+    /// Loads every chunk within `reach` chunks of a chunk
+    fn load_around(&mut self, chunk: IVec2, reach: i32) -> Result<()> {
+        if self.store.is_none() {
+            return Ok(());
+        }
+        for y in -reach..=reach {
+            for x in -reach..=reach {
+                self.load_chunk(chunk + IVec2::new(x, y))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves a chunk to the store and removes it from memory, chunks that are still generating
+    /// can't be unloaded
+    pub fn unload_chunk(&mut self, chunk: IVec2) -> Result<()> {
+        if self.generated_chunks.get(&chunk) == Some(&ChunkState::Scheduled) {
+            return Err(anyhow!(
+                "Can't unload chunk {:?} while it is generating",
+                chunk
+            ));
+        }
+        if !self.is_loaded(chunk) {
+            return Ok(());
+        }
+        let store = self
+            .store
+            .as_mut()
+            .ok_or_else(|| anyhow!("No chunk store to unload chunk {:?} to", chunk))?;
+
+        let stored = StoredChunk {
+            state: self.generated_chunks.get(&chunk).copied(),
+            cells: self.chunks.get(&chunk).cloned(),
+            overlap: self.overlaps.get(&chunk).cloned(),
+        };
+        store.save(chunk, &stored)?;
+        self.missing.remove(&chunk);
+
+        self.generated_chunks.remove(&chunk);
+        self.chunks.remove(&chunk);
+        self.overlaps.remove(&chunk);
+
+        Ok(())
+    }
+
+    /// Unloads every chunk more than `radius` chunks away from `center` that isn't generating,
+    /// call this as the area of interest moves to keep memory bounded
+    pub fn unload_outside(&mut self, center: IVec2, radius: i32) -> Result<()> {
+        let mut loaded = Vec::new();
+        loaded.extend(self.chunks.keys().copied());
+        loaded.extend(self.overlaps.keys().copied());
+        loaded.extend(self.generated_chunks.keys().copied());
+
+        for chunk in loaded {
+            let distance = (chunk - center).abs().max_element();
+            if distance > radius
+                && self.generated_chunks.get(&chunk) != Some(&ChunkState::Scheduled)
+            {
+                self.unload_chunk(chunk)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Chunks can only be generated once none of the chunks they share cells with are generating
+    fn is_ready(&self, chunk: IVec2) -> bool {
+        match self.generated_chunks.get(&chunk) {
+            Some(ChunkState::Scheduled) | Some(ChunkState::Done) => return false,
+            Some(ChunkState::Failed) | None => {}
+        }
+
+        let reach = self.reach() * 2;
+        for y in -reach..=reach {
+            for x in -reach..=reach {
+                let neighbor = chunk + IVec2::new(x, y);
+                if self.generated_chunks.get(&neighbor) == Some(&ChunkState::Scheduled) {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    fn queue_chunk(&mut self, backend: &mut dyn Backend, chunk: IVec2) -> Result<()> {
+        self.origin.get_or_insert(chunk);
+        self.generated_chunks.insert(chunk, ChunkState::Scheduled);
+        let metadata: Metadata = Some(Arc::new(chunk));
+        let attempt = self.attempts.get(&chunk).copied().unwrap_or(0);
+        let seed = world::chunk_seed(self.seed, chunk, attempt);
+        let task = WfcTask {
+            graph: self.extract_chunk(chunk),
+            tileset: self.tileset.clone(),
+            seed,
+            metadata,
+            settings: self.settings.clone(),
+            update_channel: None,
+        };
+
+        self.outstanding += 1;
+        backend.queue_task(task)
+    }
+
+    /// Returns true if every neighbor between the chunk and the origin is generated and no
+    /// neighbor on the far side is, like `GenerationMode::NonDeterministic` growing outwards from
+    /// the origin avoids squeezing chunks between independently generated chunks
+    fn is_attached(&self, chunk: IVec2) -> bool {
+        let origin = match self.origin {
+            Some(origin) => origin,
+            None => return false,
+        };
+        let toward_origin = (origin - chunk).signum();
+        if toward_origin == IVec2::ZERO {
+            return false;
+        }
+
+        for direction in 0..4 {
+            let offset = Direction::from(direction).to_ivec2();
+            let done = self.generated_chunks.get(&(chunk + offset)) == Some(&ChunkState::Done);
+            let facing_origin = (offset.x != 0 && offset.x == toward_origin.x)
+                || (offset.y != 0 && offset.y == toward_origin.y);
+            let away_from_origin = (offset.x != 0 && offset.x == -toward_origin.x)
+                || (offset.y != 0 && offset.y == -toward_origin.y);
+
+            if (facing_origin && !done) || (away_from_origin && done) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// State of the dependencies of a chunk in `GenerationMode::Deterministic`, failed if any of
+    /// them failed and done once all of them are done
+    fn dependency_state(&self, chunk: IVec2) -> Option<ChunkState> {
+        let mut state = ChunkState::Done;
+        for dependency in ChunkType::from_coordinates(chunk).dependencies(chunk) {
+            match self.generated_chunks.get(&dependency) {
+                Some(ChunkState::Done) => {}
+                Some(ChunkState::Failed) => return Some(ChunkState::Failed),
+                Some(ChunkState::Scheduled) => state = ChunkState::Scheduled,
+                None => return None,
+            }
+        }
+        Some(state)
+    }
+
+    /// Queues every waiting chunk that is able to be generated
+    fn queue_ready(&mut self, backend: &mut dyn Backend) -> Result<()> {
+        let mut waiting = VecDeque::new();
+        while let Some(chunk) = self.requested.pop_front() {
+            self.load_around(chunk, (self.reach() * 2).max(1))?;
+            match self.generated_chunks.get(&chunk) {
+                Some(ChunkState::Done) | Some(ChunkState::Scheduled) => continue,
+                Some(ChunkState::Failed) | None => {}
+            }
+            let ready = match self.generation_mode {
+                GenerationMode::NonDeterministic => self.is_ready(chunk) && self.is_attached(chunk),
+                GenerationMode::Deterministic => match self.dependency_state(chunk) {
+                    Some(ChunkState::Done) => true,
+                    // the chunk can't be generated until its dependencies are requested again
+                    Some(ChunkState::Failed) => continue,
+                    Some(ChunkState::Scheduled) | None => false,
+                },
+            };
+            if ready {
+                self.queue_chunk(backend, chunk)?;
+            } else if !waiting.contains(&chunk) {
+                waiting.push_back(chunk);
+            }
+        }
+
+        // start a new region from the first waiting chunk once nothing else can make progress
+        if self.outstanding == 0 && self.generation_mode == GenerationMode::NonDeterministic {
+            if let Some(index) = waiting.iter().position(|chunk| self.is_ready(*chunk)) {
+                let chunk = waiting.remove(index).unwrap();
+                self.queue_chunk(backend, chunk)?;
+            }
+        }
+        self.requested = waiting;
+
+        Ok(())
+    }
+
+    /// Requests chunks to be generated in order, chunks that are already generated are ignored
+    /// and failed chunks are retried with a new seed
+    pub fn request_chunks(
+        &mut self,
+        backend: &mut dyn Backend,
+        chunks: impl IntoIterator<Item = IVec2>,
+    ) -> Result<()> {
+        // budgets for the whole world can't be split between an unbounded number of chunks
+        if !self.settings.counts.is_empty() {
+            return Err(anyhow!("Tile counts are not supported by InfiniteWorld"));
+        }
+        if self.settings.path.is_some() {
+            return Err(anyhow!(
+                "The path constraint is not supported by InfiniteWorld"
+            ));
+        }
+        for chunk in chunks {
+            if self.generation_mode == GenerationMode::Deterministic {
+                self.request_dependencies(chunk)?;
+            }
+            self.requested.push_back(chunk);
+        }
+        self.queue_ready(backend)
+    }
+
+    /// Requests the dependencies of a chunk that aren't generated yet, dependencies come first
+    fn request_dependencies(&mut self, chunk: IVec2) -> Result<()> {
+        self.load_around(chunk, 1)?;
+        for dependency in ChunkType::from_coordinates(chunk).dependencies(chunk) {
+            match self.generated_chunks.get(&dependency) {
+                Some(ChunkState::Done) | Some(ChunkState::Scheduled) => {}
+                Some(ChunkState::Failed) | None => {
+                    self.request_dependencies(dependency)?;
+                    self.requested.push_back(dependency);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Generates every chunk overlapping the cells from `region.min` up to but excluding
+    /// `region.max` and blocks until they are done, use `GenerationMode::Deterministic` to get the
+    /// same tiles independent of previously generated regions
+    pub fn generate_region(&mut self, backend: &mut dyn Backend, region: IRect) -> Result<()> {
+        let size = IVec2::splat(self.chunk_settings.size as i32);
+        let min = region.min.div_euclid(size);
+        let max = (region.max - IVec2::ONE).div_euclid(size);
+
+        let mut chunks = Vec::new();
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                chunks.push(IVec2::new(x, y));
+            }
+        }
+
+        self.request_chunks(backend, chunks.iter().copied())?;
+        self.wait_for_requests(backend)?;
+
+        // chunks depending on failed chunks are dropped without being generated
+        let missing = chunks
+            .into_iter()
+            .filter(|chunk| self.generated_chunks.get(chunk) != Some(&ChunkState::Done))
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Failed to generate chunks {:?}", missing))
+        }
+    }
+
+    /// Merges finished chunks without blocking and queues chunks that became ready, returns the
+    /// chunks that finished
+    pub fn handle_output(&mut self, backend: &mut dyn Backend) -> Result<Vec<(IVec2, Result<()>)>> {
+        let mut finished = Vec::new();
+        while let Some((task, result)) = backend.get_output() {
+            finished.push(self.finish_chunk(task, result)?);
+        }
+        self.queue_ready(backend)?;
+
+        Ok(finished)
+    }
+
+    /// Blocks until every requested chunk has finished, fails if any of them failed
+    pub fn wait_for_requests(&mut self, backend: &mut dyn Backend) -> Result<()> {
+        let mut failed = Vec::new();
+        while self.outstanding > 0 {
+            let (task, result) = backend.wait_for_output();
+            let (chunk, result) = self.finish_chunk(task, result)?;
+            if let Err(e) = result {
+                error!("Failed to generate chunk {:?}: {:?}", chunk, e);
+                failed.push(chunk);
+            }
+            self.queue_ready(backend)?;
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Failed to generate chunks {:?}", failed))
+        }
+    }
+
+    fn finish_chunk(&mut self, task: WfcTask, result: Result<()>) -> Result<(IVec2, Result<()>)> {
+        self.outstanding -= 1;
+
+        let chunk = *task
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.downcast_ref::<IVec2>())
+            .expect("Task was not queued by an InfiniteWorld");
+
+        // neighbors might have been unloaded while the chunk was generating
+        self.load_around(chunk, self.reach().max(1))?;
+        self.merge_chunk(chunk, task.graph);
+        let state = match result {
+            Ok(()) => ChunkState::Done,
+            Err(_) => {
+                *self.attempts.entry(chunk).or_insert(0) += 1;
+                ChunkState::Failed
+            }
+        };
+        self.generated_chunks.insert(chunk, state);
+
+        Ok((chunk, result))
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{
+    grid_graph::{self, Borders, GridGraphSettings},
+    overlapping_tileset::OverlappingTileset,
+    world::hash_seed,
+};
+use anyhow::{anyhow, bail, Result};
+use bevy::prelude::*;
+use core_wfc::{
+    wfc_backend::Backend,
+    wfc_task::{InvalidInitialState, WfcSettings},
+    Graph, TileSet, WaveFunction, WfcTask,
+};
+use std::sync::Arc;
+
+/// A finer layer of a hierarchical generation, every cell of the layer above covers a block of
+/// `scale` by `scale` cells of this layer
+#[derive(Clone)]
+pub struct Layer {
+    pub tileset: Arc<dyn TileSet>,
+    pub scale: usize,
+    /// Tiles of this layer allowed in the block under each tile of the layer above, indexed by
+    /// the tile of the layer above
+    pub mapping: Vec<WaveFunction>,
+}
+
+/// Maps every coarse tile to the fine tiles that share one of its tags, coarse tiles without a
+/// tag the fine tileset knows allow every fine tile
+pub fn mapping_from_tags(coarse: &dyn TileSet, fine: &dyn TileSet) -> Vec<WaveFunction> {
+    let fine_tags = fine.get_tags();
+    let mut mapping = vec![WaveFunction::empty(); coarse.tile_count()];
+    for (tag, coarse_tiles) in coarse.get_tags().iter() {
+        if let Some(fine_tiles) = fine_tags.get(tag) {
+            for tile in coarse_tiles.tile_iter() {
+                mapping[tile] = WaveFunction::join(&mapping[tile], fine_tiles);
+            }
+        }
+    }
+
+    for tiles in mapping.iter_mut() {
+        if tiles.count_bits() == 0 {
+            *tiles = WaveFunction::filled(fine.tile_count());
+        }
+    }
+    mapping
+}
+
+/// Generates the coarsest layer with `tileset` and `settings` and then every layer of `layers` in
+/// order, each restricted by the layer above it
+///
+/// Layers are solved one at a time as a single task on the backend, the graphs of every layer
+/// that was attempted are returned along with the result, so the last graph failed on errors.
+/// Borders, tile counts and the path only apply to the coarsest layer as they are tiles of its
+/// tileset
+pub fn generate_layers(
+    tileset: Arc<dyn TileSet>,
+    backend: &mut dyn Backend,
+    settings: GridGraphSettings,
+    seed: u64,
+    wfc_settings: WfcSettings,
+    layers: &[Layer],
+) -> (Vec<Graph<WaveFunction>>, Result<()>) {
+    let mut graphs = Vec::new();
+    let result = generate(
+        tileset,
+        backend,
+        settings,
+        seed,
+        &wfc_settings,
+        layers,
+        &mut graphs,
+    );
+    (graphs, result)
+}
+
+fn generate(
+    tileset: Arc<dyn TileSet>,
+    backend: &mut dyn Backend,
+    mut settings: GridGraphSettings,
+    seed: u64,
+    wfc_settings: &WfcSettings,
+    layers: &[Layer],
+    graphs: &mut Vec<Graph<WaveFunction>>,
+) -> Result<()> {
+    let graph = create_graph(tileset.as_ref(), &settings);
+    solve(
+        backend,
+        graph,
+        tileset.clone(),
+        seed,
+        wfc_settings.clone(),
+        0,
+        settings.width,
+        graphs,
+    )?;
+
+    let mut coarse_tiles = tileset.tile_count();
+    for (index, layer) in layers.iter().enumerate() {
+        if layer.mapping.len() != coarse_tiles {
+            bail!(
+                "Mapping of layer {} has {} entries for {} tiles",
+                index + 1,
+                layer.mapping.len(),
+                coarse_tiles
+            );
+        }
+        if layer.scale == 0 {
+            bail!("Layer {} has a scale of 0", index + 1);
+        }
+
+        let coarse_width = settings.width;
+        settings = GridGraphSettings {
+            width: settings.width * layer.scale,
+            height: settings.height * layer.scale,
+            borders: Borders::default(),
+            ..settings
+        };
+
+        let coarse = graphs.last().unwrap();
+        let mut graph = create_graph(layer.tileset.as_ref(), &settings);
+        // Note: Assumes that the graph is a grid graph with a standard ordering
+        for y in 0..settings.height {
+            for x in 0..settings.width {
+                let coarse_index = y / layer.scale * coarse_width + x / layer.scale;
+                let coarse_tile = coarse.tiles[coarse_index]
+                    .collapse()
+                    .expect("Layer above is not collapsed");
+                let index = y * settings.width + x;
+                graph.tiles[index] =
+                    WaveFunction::intersect(&graph.tiles[index], &layer.mapping[coarse_tile]);
+            }
+        }
+
+        let layer_seed = hash_seed(seed, &[index as u32 + 1]);
+        // counts and paths are given in tiles of the coarsest tileset
+        let layer_settings = WfcSettings {
+            counts: Vec::new(),
+            path: None,
+            ..wfc_settings.clone()
+        };
+        solve(
+            backend,
+            graph,
+            layer.tileset.clone(),
+            layer_seed,
+            layer_settings,
+            index + 1,
+            settings.width,
+            graphs,
+        )?;
+        coarse_tiles = layer.tileset.tile_count();
+    }
+
+    Ok(())
+}
+
+/// Graph of a layer with the neighborhood its tileset was built for, restricted by the borders
+fn create_graph(tileset: &dyn TileSet, settings: &GridGraphSettings) -> Graph<WaveFunction> {
+    let filled = WaveFunction::filled(tileset.tile_count());
+    let mut graph = match tileset.as_any().downcast_ref::<OverlappingTileset>() {
+        Some(tileset) => tileset.create_graph(settings, filled),
+        None => grid_graph::create(settings, filled),
+    };
+    if !settings.periodic {
+        let size = IVec2::new(settings.width as i32, settings.height as i32);
+        let borders = &settings.borders;
+        borders.apply(tileset, &mut graph, IVec2::ZERO, size, size);
+    }
+    graph
+}
+
+/// Runs a single layer on the backend and pushes its graph, whether or not it succeeded
+#[allow(clippy::too_many_arguments)]
+fn solve(
+    backend: &mut dyn Backend,
+    graph: Graph<WaveFunction>,
+    tileset: Arc<dyn TileSet>,
+    seed: u64,
+    wfc_settings: WfcSettings,
+    layer: usize,
+    width: usize,
+    graphs: &mut Vec<Graph<WaveFunction>>,
+) -> Result<()> {
+    backend.queue_task(WfcTask {
+        graph,
+        tileset,
+        seed,
+        metadata: None,
+        settings: WfcSettings {
+            progress_updates: None,
+            ..wfc_settings
+        },
+        update_channel: None,
+    })?;
+
+    let (task, result) = backend.wait_for_output();
+    graphs.push(task.graph);
+    result.map_err(|e| match e.downcast_ref::<InvalidInitialState>() {
+        Some(&InvalidInitialState { node }) => anyhow!(
+            "Invalid initial state at cell {} of layer {}",
+            IVec2::new((node % width) as i32, (node / width) as i32),
+            layer
+        ),
+        None => anyhow!("Failed to generate layer {}: {}", layer, e),
+    })
+}
@@ -1,8 +1,19 @@
 pub mod basic_tileset;
+pub mod big_tile;
 pub mod carcassonne_tileset;
+pub mod chunk_store;
+pub mod chunked_generator;
+pub mod grid3d_graph;
 pub mod grid_graph;
+pub mod hex_graph;
+pub mod hex_tileset;
+pub mod infinite_world;
+pub mod layered;
 pub mod mxgmn_tileset;
-// pub mod overlapping_graph;
+pub mod overlapping_graph;
 pub mod overlapping_tileset;
 pub mod single_shot;
+pub mod vox;
+pub mod voxel_tileset;
 pub mod world;
+pub mod world3d;
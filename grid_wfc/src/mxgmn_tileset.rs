@@ -1,9 +1,12 @@
-use crate::grid_graph::Direction;
-use anyhow::Result;
+use crate::{
+    big_tile::{add_big_tiles, BigTile},
+    grid_graph::Direction,
+};
+use anyhow::{anyhow, bail, Result};
 use bevy::{prelude::*, utils::HashMap};
-use core_wfc::{TileRender, TileSet, WaveFunction};
+use core_wfc::{TileGroup, TileRender, TileSet, WaveFunction};
 use serde::Deserialize;
-use std::{any::Any, path::Path, sync::Arc};
+use std::{any::Any, collections::BTreeMap, path::Path, sync::Arc};
 
 #[derive(Debug, Clone)]
 pub struct MxgmnTileset {
@@ -11,18 +14,24 @@ pub struct MxgmnTileset {
     constraints: Arc<Vec<Vec<WaveFunction>>>,
     weights: Arc<Vec<f32>>,
     tile_paths: Vec<(TileRender, Transform)>,
+    tags: Arc<BTreeMap<String, WaveFunction>>,
+    groups: Arc<Vec<TileGroup>>,
+    needs_neighbor: Arc<Vec<WaveFunction>>,
 }
 
 impl MxgmnTileset {
     // based off https://github.com/mxgmn/WaveFunctionCollapse/blob/master/SimpleTiledModel.cs
     pub fn new(path: &Path, subset_name: Option<String>) -> Result<Self> {
-        let name = path.file_stem().unwrap().to_str().unwrap();
-        let image_folder = path.parent().unwrap().join(name);
-
         let xml =
             std::fs::read_to_string(path).expect(&format!("File {} not found!", path.display()));
         let config: Config = serde_xml_rs::from_str(&xml).unwrap();
 
+        let name = match &config.images {
+            Some(images) => images.as_str(),
+            None => path.file_stem().unwrap().to_str().unwrap(),
+        };
+        let image_folder = path.parent().unwrap().join(name);
+
         let mut subset: Vec<String> = config.tiles.tile.iter().map(|t| t.name.clone()).collect();
         if let Some(subset_name) = subset_name {
             let subsets = config.subsets.ok_or(anyhow::anyhow!("subset not found"))?;
@@ -38,6 +47,8 @@ impl MxgmnTileset {
         let mut first_occurrence = HashMap::new();
         let mut weights = Vec::new();
         let mut tile_paths = Vec::new();
+        let mut tags: BTreeMap<String, WaveFunction> = BTreeMap::new();
+        let mut variants: HashMap<String, WaveFunction> = HashMap::new();
         for tile in config.tiles.tile.iter() {
             if !subset.contains(&tile.name) {
                 continue;
@@ -120,11 +131,93 @@ impl MxgmnTileset {
                     ));
                 }
                 weights.push(tile.weight);
+                variants
+                    .entry(tile.name.clone())
+                    .or_insert_with(WaveFunction::empty)
+                    .add_tile(base + t);
+
+                // tiles are tagged with their name and the space separated `tags` attribute
+                let names = tile.tags.split_whitespace().chain([tile.name.as_str()]);
+                for name in names {
+                    tags.entry(name.to_string())
+                        .or_insert_with(WaveFunction::empty)
+                        .add_tile(base + t);
+                }
+            }
+        }
+
+        // groups form a hierarchy over the tiles, each has its listed tiles and those of its children
+        let mut groups: Vec<TileGroup> = Vec::new();
+        let mut members: Vec<Vec<String>> = Vec::new();
+        for group in config.groups.iter().flat_map(|groups| groups.group.iter()) {
+            if config.tiles.tile.iter().any(|tile| tile.name == group.name) {
+                bail!("group {} has the name of a tile", group.name);
+            }
+            let parent = match group.parent.as_str() {
+                "" => None,
+                parent => Some(
+                    groups
+                        .iter()
+                        .position(|group| group.name == parent)
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "parent {} of group {} is not declared before it",
+                                parent,
+                                group.name
+                            )
+                        })?,
+                ),
+            };
+
+            let mut group_tiles = WaveFunction::empty();
+            for name in group.tiles.split_whitespace() {
+                if !config.tiles.tile.iter().any(|tile| tile.name == name) {
+                    bail!("unknown tile {} in group {}", name, group.name);
+                }
+                if let Some(tiles) = variants.get(name) {
+                    group_tiles = WaveFunction::join(&group_tiles, tiles);
+                }
+            }
+            groups.push(TileGroup {
+                name: group.name.clone(),
+                parent,
+                tiles: group_tiles,
+            });
+            members.push(group.tiles.split_whitespace().map(String::from).collect());
+        }
+        // children come after their parent so going backwards completes them before their parent
+        for index in (0..groups.len()).rev() {
+            if let Some(parent) = groups[index].parent {
+                groups[parent].tiles =
+                    WaveFunction::join(&groups[parent].tiles, &groups[index].tiles);
+                let child = members[index].clone();
+                members[parent].extend(child);
             }
         }
+        // painting and the other tag users see groups as tags
+        for group in groups.iter() {
+            let tag = tags
+                .entry(group.name.clone())
+                .or_insert_with(WaveFunction::empty);
+            *tag = WaveFunction::join(tag, &group.tiles);
+        }
 
+        // neighbor rules can name a group instead of a tile, the rule then applies to every tile
+        // in it with the same rotation
+        let expand = |name: &str| match groups.iter().position(|group| group.name == name) {
+            Some(index) => members[index].clone(),
+            None => vec![name.to_string()],
+        };
+
+        // diagonal rules add constraints for the diagonal directions, which are left out otherwise
         let tile_count = action.len();
-        let mut constraints = vec![vec![WaveFunction::empty(); 4]; tile_count];
+        let diagonals = config
+            .neighbors
+            .neighbor
+            .iter()
+            .any(|neighbor| neighbor.diagonal);
+        let direction_count = if diagonals { 8 } else { 4 };
+        let mut constraints = vec![vec![WaveFunction::empty(); direction_count]; tile_count];
         for neighbor in config.neighbors.neighbor.iter() {
             let mut left = neighbor.left.split(' ');
             let mut right = neighbor.right.split(' ');
@@ -141,29 +234,49 @@ impl MxgmnTileset {
                     .and_then(|f| f.parse::<usize>().ok())
                     .unwrap_or(0),
             );
-            if !subset.contains(&left.0.to_string()) || !subset.contains(&right.0.to_string()) {
-                continue;
+            for left_name in expand(left.0) {
+                for right_name in expand(right.0) {
+                    if !subset.contains(&left_name) || !subset.contains(&right_name) {
+                        continue;
+                    }
+
+                    let l = action[first_occurrence[&left_name]][left.1];
+                    let r = action[first_occurrence[&right_name]][right.1];
+                    if neighbor.diagonal {
+                        // the right tile is up and to the right of the left one, the rule holds
+                        // for every rotation and reflection of the pair
+                        for (k, (&l, &r)) in action[l].iter().zip(action[r].iter()).enumerate() {
+                            let mut offset = IVec2::new(-1, -1);
+                            for _ in 0..k % 4 {
+                                offset = IVec2::new(-offset.y, offset.x);
+                            }
+                            if k >= 4 {
+                                offset.x = -offset.x;
+                            }
+                            constraints[r][Direction::from_ivec2(offset) as usize].add_tile(l);
+                        }
+                        continue;
+                    }
+
+                    let d = action[l][1];
+                    let u = action[r][1];
+
+                    constraints[r][2].add_tile(l);
+                    constraints[action[r][6]][2].add_tile(action[l][6]);
+                    constraints[action[l][4]][2].add_tile(action[r][4]);
+                    constraints[action[l][2]][2].add_tile(action[r][2]);
+
+                    constraints[u][1].add_tile(d);
+                    constraints[action[d][6]][1].add_tile(action[u][6]);
+                    constraints[action[u][4]][1].add_tile(action[d][4]);
+                    constraints[action[d][2]][1].add_tile(action[u][2]);
+                }
             }
-
-            let l = action[first_occurrence[left.0]][left.1];
-            let d = action[l][1];
-            let r = action[first_occurrence[right.0]][right.1];
-            let u = action[r][1];
-
-            constraints[r][2].add_tile(l);
-            constraints[action[r][6]][2].add_tile(action[l][6]);
-            constraints[action[l][4]][2].add_tile(action[r][4]);
-            constraints[action[l][2]][2].add_tile(action[r][2]);
-
-            constraints[u][1].add_tile(d);
-            constraints[action[d][6]][1].add_tile(action[u][6]);
-            constraints[action[u][4]][1].add_tile(action[d][4]);
-            constraints[action[d][2]][1].add_tile(action[u][2]);
         }
 
         // make sure all constraints are reciprocal
         for i in 0..tile_count {
-            for direction in 0..4 {
+            for direction in 0..direction_count {
                 for allowed in constraints[i][direction].clone().tile_iter() {
                     let other_direction = Direction::from(direction).other();
                     constraints[allowed][other_direction as usize].add_tile(i);
@@ -173,7 +286,7 @@ impl MxgmnTileset {
 
         // make sure no constraint is empty
         for i in 0..tile_count {
-            for direction in 0..4 {
+            for direction in 0..direction_count {
                 if constraints[i][direction].count_bits() == 0 {
                     println!(
                         "empty constraint found: Tile: {} Direction: {}",
@@ -183,6 +296,61 @@ impl MxgmnTileset {
             }
         }
 
+        // big tiles are like a tile of the set on each part, given as `name rotation` separated
+        // by commas
+        let mut big_tiles = Vec::new();
+        for big_tile in config
+            .big_tiles
+            .iter()
+            .flat_map(|big_tiles| big_tiles.big_tile.iter())
+        {
+            if config
+                .tiles
+                .tile
+                .iter()
+                .any(|tile| tile.name == big_tile.name)
+            {
+                bail!("big tile {} has the name of a tile", big_tile.name);
+            }
+            let mut like = Vec::new();
+            for part in big_tile.like.split(',') {
+                let mut part = part.split_whitespace();
+                let name = part.next().unwrap_or_default();
+                let rotation = part
+                    .next()
+                    .and_then(|f| f.parse::<usize>().ok())
+                    .unwrap_or(0);
+                if !config.tiles.tile.iter().any(|tile| tile.name == name) {
+                    bail!("big tile {} is like unknown tile {}", big_tile.name, name);
+                }
+                if let Some(&base) = first_occurrence.get(name) {
+                    like.push(action[base][rotation]);
+                }
+            }
+            // big tiles like tiles outside of the subset are left out with them
+            if like.len() != big_tile.like.split(',').count() {
+                continue;
+            }
+            let path = image_folder.join(format!("{}.png", big_tile.name));
+            big_tiles.push(BigTile {
+                name: big_tile.name.clone(),
+                size: UVec2::new(big_tile.width, big_tile.height),
+                weight: big_tile.weight,
+                like,
+                render: TileRender::Sprite(path.to_str().unwrap().to_string()),
+            });
+        }
+        let mut needs_neighbor = Vec::new();
+        add_big_tiles(
+            &big_tiles,
+            &mut constraints,
+            &mut weights,
+            &mut tile_paths,
+            &mut tags,
+            &mut needs_neighbor,
+        )?;
+        let tile_count = constraints.len();
+
         // println!("{a:?}");
         // println!("{b:?}");
         // println!("{}", a == b);
@@ -206,6 +374,9 @@ impl MxgmnTileset {
             constraints: Arc::new(constraints),
             weights: Arc::new(weights),
             tile_paths,
+            tags: Arc::new(tags),
+            groups: Arc::new(groups),
+            needs_neighbor: Arc::new(needs_neighbor),
         })
     }
 }
@@ -231,6 +402,18 @@ impl TileSet for MxgmnTileset {
         self.tile_paths.clone()
     }
 
+    fn get_tags(&self) -> Arc<BTreeMap<String, WaveFunction>> {
+        self.tags.clone()
+    }
+
+    fn get_groups(&self) -> Arc<Vec<TileGroup>> {
+        self.groups.clone()
+    }
+
+    fn get_needs_neighbor(&self) -> Arc<Vec<WaveFunction>> {
+        self.needs_neighbor.clone()
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -241,9 +424,14 @@ impl TileSet for MxgmnTileset {
 struct Config {
     #[serde(default)]
     unique: bool,
+    /// Folder of the images next to the xml file, defaults to the name of the file
+    images: Option<String>,
     tiles: Tiles,
     neighbors: Neighbors,
     subsets: Option<Subsets>,
+    groups: Option<Groups>,
+    #[serde(rename = "bigtiles")]
+    big_tiles: Option<BigTiles>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -257,6 +445,8 @@ struct Tile {
     symmetry: char,
     #[serde(default = "default_weight")]
     weight: f32,
+    #[serde(default)]
+    tags: String,
 }
 
 fn default_weight() -> f32 {
@@ -272,6 +462,9 @@ struct Neighbors {
 struct Neighbor {
     left: String,
     right: String,
+    /// The right tile is diagonally up and to the right of the left tile instead of next to it
+    #[serde(default)]
+    diagonal: bool,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -290,3 +483,33 @@ struct Subset {
 struct TileName {
     name: String,
 }
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Groups {
+    group: Vec<Group>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Group {
+    name: String,
+    #[serde(default)]
+    parent: String,
+    #[serde(default)]
+    tiles: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct BigTiles {
+    #[serde(rename = "bigtile")]
+    big_tile: Vec<BigTileConfig>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct BigTileConfig {
+    name: String,
+    width: u32,
+    height: u32,
+    #[serde(default = "default_weight")]
+    weight: f32,
+    like: String,
+}
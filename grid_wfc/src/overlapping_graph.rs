@@ -1,3 +1,4 @@
+use crate::grid_graph::Direction;
 use bevy::prelude::*;
 use core_wfc::{Graph, Neighbor};
 
@@ -21,6 +22,27 @@ impl Default for OverlappingGraphSettings {
     }
 }
 
+/// Offsets of all neighbors within the pattern window, the first four or eight match
+/// `grid_graph::Direction` so tilesets built for the full neighborhood still work on grid graphs
+pub fn directions(overlap: usize) -> Vec<IVec2> {
+    let direction_count = if overlap > 0 { 8 } else { 4 };
+    let mut directions = (0..direction_count)
+        .map(|direction| Direction::from(direction).to_ivec2())
+        .collect::<Vec<_>>();
+
+    let overlap = overlap as i32;
+    for y in -overlap..=overlap {
+        for x in -overlap..=overlap {
+            let direction = IVec2::new(x, y);
+            if direction != IVec2::ZERO && !directions.contains(&direction) {
+                directions.push(direction);
+            }
+        }
+    }
+
+    directions
+}
+
 pub fn create<F: Clone>(settings: &OverlappingGraphSettings, fill_with: F) -> Graph<F> {
     let size = IVec2::new(settings.width as i32, settings.height as i32);
 
@@ -31,30 +53,27 @@ pub fn create<F: Clone>(settings: &OverlappingGraphSettings, fill_with: F) -> Gr
         }
     }
 
-    let overlap = settings.overlap as i32;
-    let overlap_width = overlap * 2 + 1;
+    let directions = directions(settings.overlap);
+
     let mut neighbors = Vec::new();
     for pos in nodes_pos.iter() {
         let mut node_neighbors = Vec::new();
-        for y in -overlap..=overlap {
-            for x in -overlap..=overlap {
-                let mut neighbor_pos = *pos + IVec2::new(x, y);
-                if settings.periodic {
-                    neighbor_pos = IVec2::new(
-                        neighbor_pos.x.rem_euclid(size.x),
-                        neighbor_pos.y.rem_euclid(size.y),
-                    );
-                } else if neighbor_pos.cmpge(size).any() || neighbor_pos.cmplt(IVec2::ZERO).any() {
-                    continue;
-                }
-
-                let neighbor_index = (neighbor_pos.y * size.x + neighbor_pos.x) as usize;
-                let direction_index = overlap_width * (x + overlap) + (y + overlap);
-                node_neighbors.push(Neighbor {
-                    direction: direction_index as usize,
-                    index: neighbor_index,
-                });
+        for (i, dir_vec) in directions.iter().enumerate() {
+            let mut neighbor_pos = *pos + *dir_vec;
+            if settings.periodic {
+                neighbor_pos = IVec2::new(
+                    neighbor_pos.x.rem_euclid(size.x),
+                    neighbor_pos.y.rem_euclid(size.y),
+                );
+            } else if neighbor_pos.cmpge(size).any() || neighbor_pos.cmplt(IVec2::ZERO).any() {
+                continue;
             }
+
+            let neighbor_index = (neighbor_pos.y * size.x + neighbor_pos.x) as usize;
+            node_neighbors.push(Neighbor {
+                direction: i,
+                index: neighbor_index,
+            });
         }
         neighbors.push(node_neighbors);
     }
@@ -1,31 +1,77 @@
+use crate::{
+    grid_graph::{self, GridGraphSettings},
+    overlapping_graph::{self, OverlappingGraphSettings},
+};
+use anyhow::Result;
 use bevy::{prelude::*, utils::HashMap};
-use core_wfc::{TileRender, TileSet, WaveFunction};
-use std::{any::Any, sync::Arc, path::Path};
+use core_wfc::{Graph, TileRender, TileSet, WaveFunction};
+use image::{Rgba, RgbaImage};
+use std::{any::Any, collections::BTreeMap, path::Path, sync::Arc};
 
 #[derive(Hash, PartialEq, Eq, Debug, Clone)]
 struct Pattern {
     tiles: Vec<usize>,
 }
 
+#[derive(Debug, Clone)]
+pub struct OverlappingSettings {
+    /// Radius of the pattern window, patterns are `overlap * 2 + 1` tiles wide
+    pub overlap: usize,
+    /// Number of reflected and rotated variants added per pattern, follows the same rules as mxgmn
+    pub symmetry: usize,
+    /// Wrap around the edges of the sample when extracting patterns
+    pub periodic_input: bool,
+    /// Fix the bottom row of the output to the pattern found at the bottom of the sample
+    pub ground: bool,
+    /// Constrain every offset within the pattern window instead of only the four direct neighbors,
+    /// graphs have to be created with `OverlappingTileset::create_graph` to make use of this
+    pub full_neighborhood: bool,
+}
+
+impl Default for OverlappingSettings {
+    fn default() -> Self {
+        Self {
+            overlap: 1,
+            symmetry: 8,
+            periodic_input: true,
+            ground: false,
+            full_neighborhood: false,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OverlappingTileset {
     tile_count: usize,
     overlap: usize,
+    full_neighborhood: bool,
+    ground: Option<usize>,
     patterns: Arc<Vec<Pattern>>,
     constraints: Arc<Vec<Vec<WaveFunction>>>,
     weights: Arc<Vec<f32>>,
     tile_colors: Vec<Color>,
+    tags: Arc<BTreeMap<String, WaveFunction>>,
 }
 
 impl OverlappingTileset {
-    /// overlap is the radius of the overlap, symmetry follows the same rules as mxgmn
-    pub fn new(sample: Vec<Vec<usize>>, overlap: usize, symmetry: usize) -> Self {
-        let overlap = overlap as i32;
+    pub fn new(sample: Vec<Vec<usize>>, settings: &OverlappingSettings) -> Self {
+        let overlap = settings.overlap as i32;
         let size = IVec2::new(sample.len() as i32, sample[0].len() as i32);
 
-        let mut patterns_hash = HashMap::new();
-        for y in 0..size.y {
-            for x in 0..size.x {
+        // without wrapping patterns have to fit entirely inside the sample
+        let (min, max) = if settings.periodic_input {
+            (IVec2::ZERO, size)
+        } else {
+            (IVec2::splat(overlap), size - IVec2::splat(overlap))
+        };
+
+        // patterns are indexed in order of first occurrence so indices are stable between runs
+        let mut pattern_indices = HashMap::new();
+        let mut patterns = Vec::new();
+        let mut weights = Vec::new();
+        let mut ground_pattern = None;
+        for y in min.y..max.y {
+            for x in min.x..max.x {
                 let mut tiles = Vec::new();
                 for py in -overlap..=overlap {
                     for px in -overlap..=overlap {
@@ -34,44 +80,57 @@ impl OverlappingTileset {
                         tiles.push(sample[sx as usize][sy as usize]);
                     }
                 }
-                let mut pattern = Pattern { tiles };
-
-                for i in 0..symmetry {
-                    if i > 0 {
-                        if i % 2 == 1 {
-                            pattern.reflect((overlap * 2 + 1) as usize);
-                        } else {
-                            pattern.rotate((overlap * 2 + 1) as usize);
-                        }
+                let pattern = Pattern { tiles };
+
+                // like mxgmn the ground is the last pattern in the bottom row of the sample
+                if y == min.y && x == max.x - 1 {
+                    ground_pattern = Some(pattern.clone());
+                }
+
+                // like mxgmn odd variants reflect the variant before them and even variants rotate
+                // the variant two before them, so 8 variants cover every rotation and reflection
+                let mut variants = vec![pattern];
+                for i in 1..settings.symmetry {
+                    if i % 2 == 1 {
+                        let mut variant = variants[i - 1].clone();
+                        variant.reflect((overlap * 2 + 1) as usize);
+                        variants.push(variant);
+                    } else {
+                        let mut variant = variants[i - 2].clone();
+                        variant.rotate((overlap * 2 + 1) as usize);
+                        variants.push(variant);
                     }
+                }
 
-                    if let Some(weight) = patterns_hash.get_mut(&pattern) {
-                        *weight += 1.0;
+                for pattern in variants {
+                    if let Some(index) = pattern_indices.get(&pattern) {
+                        weights[*index] += 1.0;
                     } else {
-                        patterns_hash.insert(pattern.clone(), 1.0);
+                        pattern_indices.insert(pattern.clone(), patterns.len());
+                        patterns.push(pattern.clone());
+                        weights.push(1.0);
                     }
                 }
             }
         }
 
-        let mut patterns = Vec::new();
-        let mut weights = Vec::new();
-        for (pattern, weight) in patterns_hash {
-            patterns.push(pattern);
-            weights.push(weight);
-        }
+        let ground = match (settings.ground, ground_pattern) {
+            (true, Some(ground_pattern)) => pattern_indices.get(&ground_pattern).copied(),
+            _ => None,
+        };
 
         let tile_count = patterns.len();
         let pattern_width = overlap * 2 + 1;
 
-        let directions = vec![
-            IVec2::new(0, 1),
-            IVec2::new(0, -1),
-            IVec2::new(-1, 0),
-            IVec2::new(1, 0),
-        ];
+        // diagonals are included for grid graphs with `GridGraphSettings::diagonals`
+        let directions = if settings.full_neighborhood {
+            overlapping_graph::directions(settings.overlap)
+        } else {
+            overlapping_graph::directions(1)
+        };
 
-        let mut constraints = vec![vec![WaveFunction::filled(tile_count); 4]; tile_count];
+        let mut constraints =
+            vec![vec![WaveFunction::filled(tile_count); directions.len()]; tile_count];
         for (i, pattern) in patterns.iter().enumerate() {
             for (j, other) in patterns.iter().enumerate() {
                 'directions: for (k, direction) in directions.iter().enumerate() {
@@ -133,10 +192,13 @@ impl OverlappingTileset {
         Self {
             tile_count,
             overlap: overlap as usize,
+            full_neighborhood: settings.full_neighborhood,
+            ground,
             patterns: Arc::new(patterns),
             constraints: Arc::new(constraints),
             weights: Arc::new(weights),
             tile_colors,
+            tags: Arc::default(),
         }
     }
 
@@ -145,47 +207,166 @@ impl OverlappingTileset {
         (tile, self.tile_colors[tile])
     }
 
-    pub fn from_image(path: &Path, overlap: usize, symmetry: usize) -> Self {
+    /// Tiles of a pattern row by row, the center tile is at `overlap` in the middle row
+    pub fn get_pattern(&self, index: usize) -> &[usize] {
+        &self.patterns[index].tiles
+    }
+
+    /// Creates a grid graph with the neighborhood the constraints of this tileset were built for
+    pub fn create_graph<F: Clone>(&self, settings: &GridGraphSettings, fill_with: F) -> Graph<F> {
+        if self.full_neighborhood {
+            let settings = OverlappingGraphSettings {
+                width: settings.width,
+                height: settings.height,
+                overlap: self.overlap,
+                periodic: settings.periodic,
+            };
+            overlapping_graph::create(&settings, fill_with)
+        } else {
+            grid_graph::create(settings, fill_with)
+        }
+    }
+
+    /// Index of the ground pattern if the tileset was created with `ground` enabled
+    pub fn get_ground(&self) -> Option<usize> {
+        self.ground
+    }
+
+    /// Fixes the bottom row of a grid graph to the ground pattern and removes the ground pattern
+    /// from every other row, does nothing if the tileset has no ground
+    pub fn constrain_ground(&self, graph: &mut Graph<WaveFunction>, width: usize) {
+        let ground = match self.ground {
+            Some(ground) => ground,
+            None => return,
+        };
+
+        // Note: Assumes that the graph is a grid graph with a standard ordering
+        for (index, tile) in graph.tiles.iter_mut().enumerate() {
+            if index < width {
+                *tile = WaveFunction::intersect(tile, &WaveFunction::single(ground));
+            } else {
+                tile.remove_tile(ground);
+            }
+        }
+    }
+
+    pub fn from_image(path: &Path, settings: &OverlappingSettings) -> Self {
         let image = image::open(path).unwrap();
         let image = image.to_rgba8();
         let size = IVec2::new(image.width() as i32, image.height() as i32);
 
+        // colors are indexed in order of first occurrence so indices are stable between runs
         let mut tiles = HashMap::new();
+        let mut colors = Vec::new();
         for y in 0..size.y {
             for x in 0..size.x {
                 let pixel = image.get_pixel(x as u32, y as u32);
-                let color = Color::rgb(
-                    pixel[0] as f32 / 255.0,
-                    pixel[1] as f32 / 255.0,
-                    pixel[2] as f32 / 255.0,
-                );
                 let tile = (pixel[0] as usize) << 16 | (pixel[1] as usize) << 8 | pixel[2] as usize;
-                tiles.insert(tile, (color, 0));
+                tiles.entry(tile).or_insert_with(|| {
+                    colors.push(Color::rgb(
+                        pixel[0] as f32 / 255.0,
+                        pixel[1] as f32 / 255.0,
+                        pixel[2] as f32 / 255.0,
+                    ));
+                    colors.len() - 1
+                });
             }
         }
 
-        let mut colors = Vec::new();
-        for (i, (_, (color, tile_index))) in tiles.iter_mut().enumerate() {
-            *tile_index = i;
-            colors.push(*color);
-        }
-
         let mut sample = Vec::new();
         for x in 0..size.x {
             let mut column = Vec::new();
             for y in (0..size.y).rev() {
                 let pixel = image.get_pixel(x as u32, y as u32);
                 let tile = (pixel[0] as usize) << 16 | (pixel[1] as usize) << 8 | pixel[2] as usize;
-                column.push(tiles.get(&tile).unwrap().1);
+                column.push(tiles[&tile]);
             }
             sample.push(column);
         }
 
-        let mut tileset = Self::new(sample, overlap, symmetry);
+        let mut tileset = Self::new(sample, settings);
         tileset.tile_colors = colors;
 
+        // every pattern is tagged with the hex code of its center color, like `#ffffff`
+        let mut tags = BTreeMap::new();
+        for (&color, &tile) in tiles.iter() {
+            let mut mask = WaveFunction::empty();
+            for pattern in 0..tileset.tile_count {
+                if tileset.get_center_tile(pattern).0 == tile {
+                    mask.add_tile(pattern);
+                }
+            }
+            tags.insert(format!("#{:06x}", color), mask);
+        }
+        tileset.tags = Arc::new(tags);
+
         tileset
     }
+
+    /// Converts a grid graph into an image with one pixel per cell. Like mxgmn each pixel is the
+    /// average color given to it by the remaining patterns of every cell whose pattern covers it,
+    /// contradictions are transparent
+    pub fn render_image(
+        &self,
+        graph: &Graph<WaveFunction>,
+        settings: &GridGraphSettings,
+    ) -> RgbaImage {
+        let size = IVec2::new(settings.width as i32, settings.height as i32);
+        let overlap = self.overlap as i32;
+        let pattern_width = overlap * 2 + 1;
+        let mut image = RgbaImage::new(settings.width as u32, settings.height as u32);
+
+        // Note: Assumes that the graph is a grid graph with a standard ordering
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let mut sum = Vec4::ZERO;
+                let mut count = 0;
+                if graph.tiles[(y * size.x + x) as usize].count_bits() > 0 {
+                    for dy in -overlap..=overlap {
+                        for dx in -overlap..=overlap {
+                            // the pattern of the cell at (x - dx, y - dy) covers this pixel with
+                            // its tile at (dx, dy) from the center
+                            let mut pos = IVec2::new(x - dx, y - dy);
+                            if settings.periodic {
+                                pos = pos.rem_euclid(size);
+                            } else if pos.cmplt(IVec2::ZERO).any() || pos.cmpge(size).any() {
+                                continue;
+                            }
+
+                            let index = ((dy + overlap) * pattern_width + dx + overlap) as usize;
+                            let cell = &graph.tiles[(pos.y * size.x + pos.x) as usize];
+                            for pattern in cell.tile_iter() {
+                                let tile = self.patterns[pattern].tiles[index];
+                                sum += Vec4::from(self.tile_colors[tile].as_rgba_f32());
+                                count += 1;
+                            }
+                        }
+                    }
+                }
+                let color = match count {
+                    0 => Vec4::ZERO,
+                    count => sum / count as f32,
+                };
+
+                let pixel = (color * 255.0).round().to_array().map(|c| c as u8);
+                image.put_pixel(x as u32, (size.y - 1 - y) as u32, Rgba(pixel));
+            }
+        }
+
+        image
+    }
+
+    /// Renders a grid graph with `render_image` and saves it, the format is chosen based on the
+    /// extension of the path
+    pub fn save_image(
+        &self,
+        graph: &Graph<WaveFunction>,
+        settings: &GridGraphSettings,
+        path: &Path,
+    ) -> Result<()> {
+        self.render_image(graph, settings).save(path)?;
+        Ok(())
+    }
 }
 
 impl TileSet for OverlappingTileset {
@@ -217,6 +398,10 @@ impl TileSet for OverlappingTileset {
         tile_render
     }
 
+    fn get_tags(&self) -> Arc<BTreeMap<String, WaveFunction>> {
+        self.tags.clone()
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
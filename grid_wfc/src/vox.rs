@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Result};
+use bevy::prelude::*;
+use std::{fs, path::Path};
+
+const VERSION: i32 = 150;
+
+/// Saves a volume as a MagicaVoxel .vox file, `colors` are ordered like `grid3d_graph` with `None`
+/// for empty voxels
+///
+/// MagicaVoxel uses z as the up axis so a voxel at (x, y, z) is saved at (x, -z, y), offset to
+/// keep positions positive, which keeps the volume from being mirrored. The volume and the number
+/// of distinct colors are limited to 256 and 255 by the format
+pub fn save(path: &Path, size: IVec3, colors: &[Option<Color>]) -> Result<()> {
+    if size.cmpgt(IVec3::splat(256)).any() {
+        return Err(anyhow!("{:?} is too large for a .vox file", size));
+    }
+
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut voxels = Vec::new();
+    for z in 0..size.z {
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let index = ((z * size.y + y) * size.x + x) as usize;
+                let color = match colors[index] {
+                    Some(color) => color.as_rgba_u8(),
+                    None => continue,
+                };
+
+                let color_index = match palette.iter().position(|c| *c == color) {
+                    Some(i) => i,
+                    None => {
+                        if palette.len() == 255 {
+                            return Err(anyhow!("More than 255 colors can't be saved as .vox"));
+                        }
+                        palette.push(color);
+                        palette.len() - 1
+                    }
+                };
+                let vox_y = size.z - 1 - z;
+                voxels.extend_from_slice(&[x as u8, vox_y as u8, y as u8, color_index as u8 + 1]);
+            }
+        }
+    }
+
+    let mut size_chunk = Vec::new();
+    for value in [size.x, size.z, size.y] {
+        size_chunk.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut xyzi = Vec::new();
+    xyzi.extend_from_slice(&((voxels.len() / 4) as i32).to_le_bytes());
+    xyzi.extend_from_slice(&voxels);
+
+    // the palette always has 256 entries, the last one is unused
+    palette.resize(256, [0; 4]);
+    let rgba: Vec<u8> = palette.into_iter().flatten().collect();
+
+    let mut children = Vec::new();
+    write_chunk(&mut children, b"SIZE", &size_chunk, &[]);
+    write_chunk(&mut children, b"XYZI", &xyzi, &[]);
+    write_chunk(&mut children, b"RGBA", &rgba, &[]);
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"VOX ");
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    write_chunk(&mut bytes, b"MAIN", &[], &children);
+
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    bytes.extend_from_slice(id);
+    bytes.extend_from_slice(&(content.len() as i32).to_le_bytes());
+    bytes.extend_from_slice(&(children.len() as i32).to_le_bytes());
+    bytes.extend_from_slice(content);
+    bytes.extend_from_slice(children);
+}
@@ -0,0 +1,145 @@
+use crate::grid3d_graph::{Direction3d, ROTATIONS};
+use bevy::prelude::*;
+use core_wfc::{TileRender, TileSet, WaveFunction};
+use std::{any::Any, sync::Arc};
+
+const DIRECTIONS: usize = 6;
+
+/// A cube with a socket on each face in the order of `Direction3d`, tiles fit next to each other
+/// if the sockets facing each other are equal
+#[derive(Debug, Clone)]
+pub struct VoxelTile {
+    pub sockets: [usize; DIRECTIONS],
+    pub weight: f32,
+    /// Transparent tiles are left empty when saving a .vox file
+    pub color: Color,
+    /// Adds all 24 rotations of the tile, rotations that look the same are only added once
+    pub rotate: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct VoxelTileset {
+    /// Index of the original tile and the rotation of every tile
+    tiles: Vec<(usize, usize)>,
+    colors: Vec<Color>,
+    constraints: Arc<Vec<Vec<WaveFunction>>>,
+    weights: Arc<Vec<f32>>,
+}
+
+impl VoxelTileset {
+    pub fn new(tiles: &[VoxelTile]) -> Self {
+        let mut rotated_tiles = Vec::new();
+        let mut rotated_sockets: Vec<[usize; DIRECTIONS]> = Vec::new();
+        for (index, tile) in tiles.iter().enumerate() {
+            let first = rotated_sockets.len();
+            let rotations = if tile.rotate { ROTATIONS } else { 1 };
+            for rotation in 0..rotations {
+                let mut sockets = [0; DIRECTIONS];
+                for (direction, socket) in tile.sockets.iter().enumerate() {
+                    sockets[Direction3d::from(direction).rotate(rotation) as usize] = *socket;
+                }
+                if !rotated_sockets[first..].contains(&sockets) {
+                    rotated_tiles.push((index, rotation));
+                    rotated_sockets.push(sockets);
+                }
+            }
+        }
+
+        // convert to allowed neighbors
+        let mut allowed_neighbors = Vec::with_capacity(rotated_sockets.len());
+        for sockets in rotated_sockets.iter() {
+            let mut allowed_neighbors_for_tile = Vec::with_capacity(DIRECTIONS);
+            for (direction, socket) in sockets.iter().enumerate() {
+                let other_direction = Direction3d::from(direction).other() as usize;
+                let mut cell = WaveFunction::empty();
+                for (other_tile, other_sockets) in rotated_sockets.iter().enumerate() {
+                    if other_sockets[other_direction] == *socket {
+                        cell.add_tile(other_tile);
+                    }
+                }
+                allowed_neighbors_for_tile.push(cell);
+            }
+            allowed_neighbors.push(allowed_neighbors_for_tile);
+        }
+
+        // the weight of a tile is split between its rotations
+        let weights = rotated_tiles
+            .iter()
+            .map(|(index, _)| {
+                let rotations = rotated_tiles.iter().filter(|(i, _)| i == index).count();
+                tiles[*index].weight / rotations as f32
+            })
+            .collect();
+
+        Self {
+            colors: tiles.iter().map(|tile| tile.color).collect(),
+            tiles: rotated_tiles,
+            constraints: Arc::new(allowed_neighbors),
+            weights: Arc::new(weights),
+        }
+    }
+
+    /// Returns the index of the tile passed to `new` and its rotation
+    pub fn get_tile(&self, tile: usize) -> (usize, usize) {
+        self.tiles[tile]
+    }
+}
+
+impl Default for VoxelTileset {
+    /// Pipes running through empty space
+    fn default() -> Self {
+        #[derive(Clone, Copy)]
+        enum Socket {
+            Air,
+            Pipe,
+        }
+        use Socket::*;
+
+        let tile = |sockets: [Socket; DIRECTIONS], weight, color| VoxelTile {
+            sockets: sockets.map(|socket| socket as usize),
+            weight,
+            color,
+            rotate: true,
+        };
+        let air = Color::NONE;
+        let pipe = Color::rgb(0.6, 0.6, 0.65);
+        let joint = Color::rgb(0.8, 0.5, 0.2);
+
+        Self::new(&[
+            tile([Air; DIRECTIONS], 20.0, air),
+            tile([Pipe, Pipe, Air, Air, Air, Air], 2.0, pipe),
+            tile([Pipe, Air, Pipe, Air, Air, Air], 1.0, joint),
+            tile([Pipe, Pipe, Pipe, Air, Air, Air], 0.3, joint),
+            tile([Pipe, Pipe, Pipe, Pipe, Pipe, Pipe], 0.1, joint),
+        ])
+    }
+}
+
+impl TileSet for VoxelTileset {
+    fn tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+
+    fn get_constraints(&self) -> Arc<Vec<Vec<WaveFunction>>> {
+        self.constraints.clone()
+    }
+
+    fn get_weights(&self) -> Arc<Vec<f32>> {
+        self.weights.clone()
+    }
+
+    fn set_weights(&mut self, weights: Vec<f32>) {
+        self.weights = Arc::new(weights);
+    }
+
+    fn get_render_tile_assets(&self) -> Vec<(TileRender, Transform)> {
+        self.tiles
+            .iter()
+            .map(|(index, _)| (TileRender::Color(self.colors[*index]), Transform::IDENTITY))
+            .collect()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -1,11 +1,16 @@
-use crate::grid_graph::{self, Direction, GridGraphSettings};
+use crate::{
+    grid_graph::{self, Borders, Direction, GridGraphSettings},
+    overlapping_tileset::OverlappingTileset,
+};
+use anyhow::{anyhow, Result};
 use bevy::{prelude::*, utils::HashMap};
 use core_wfc::{
-    wfc_task::{Metadata, WfcSettings},
-    Graph, Neighbor, TileSet, WaveFunction,
+    wfc_backend::SingleThreaded,
+    wfc_task::{InvalidInitialState, Metadata, TileCount, WfcSettings},
+    Graph, Neighbor, TileSet, WaveFunction, WfcTask,
 };
 use crossbeam::channel::{Receiver, Sender};
-use rand::{rngs::SmallRng, Rng};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
 use std::sync::Arc;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
@@ -27,6 +32,17 @@ pub struct World {
     pub generated_chunks: HashMap<IVec2, ChunkState>,
     pub chunk_settings: ChunkSettings,
     pub tileset: Arc<dyn TileSet>,
+    /// Cells restricted by hand before generating, see `World::paint`
+    pub painted: HashMap<IVec2, WaveFunction>,
+    /// Connects cells diagonally as well, see `GridGraphSettings::diagonals`
+    pub diagonals: bool,
+    /// Rules for the cells along the edges of the world, see `GridGraphSettings::borders`
+    pub borders: Borders,
+    /// Wraps the world around so opposite edges fit together, chunks along an edge overlap the
+    /// chunks on the other side, see `World::check_periodic`
+    pub periodic: bool,
+    /// Seed of the world, chunk seeds are derived from it with `chunk_seed`
+    pub seed: u64,
     pub rng: SmallRng,
     pub outstanding: usize,
     pub settings: WfcSettings,
@@ -42,6 +58,7 @@ pub struct ChunkSettings {
     pub overlap: usize,
     pub discard: usize,
     pub merging: ChunkMerging,
+    pub recovery: ChunkRecovery,
 }
 
 impl Default for ChunkSettings {
@@ -51,10 +68,24 @@ impl Default for ChunkSettings {
             overlap: 3,
             discard: 1,
             merging: ChunkMerging::Mixed,
+            recovery: ChunkRecovery::default(),
         }
     }
 }
 
+/// What to do when a chunk fails, retries are tried first and rollbacks once they run out
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Reflect, Default)]
+pub struct ChunkRecovery {
+    /// Number of times a failed chunk is generated again with a new seed
+    pub retries: u32,
+    /// Added to the discard of a chunk on every retry so it takes more of its surroundings into
+    /// account
+    pub enlarge_discard: usize,
+    /// Number of times the finished neighbors of a chunk that ran out of retries are cleared and
+    /// generated again before the chunk itself
+    pub rollbacks: u32,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Reflect)]
 pub enum ChunkMerging {
     Interior,
@@ -62,6 +93,32 @@ pub enum ChunkMerging {
     Mixed,
 }
 
+impl ChunkMerging {
+    /// Returns true if a world tile should be copied into a chunk when extracting it
+    pub fn should_extract(&self, inside_chunk: bool) -> bool {
+        match self {
+            ChunkMerging::Mixed => !inside_chunk,
+            ChunkMerging::Interior | ChunkMerging::Full => true,
+        }
+    }
+
+    /// Returns true if a tile generated by a chunk should overwrite the world tile, tiles inside
+    /// the chunk are overwritten while tiles on the border are preserved
+    pub fn should_merge(
+        &self,
+        inside_chunk: bool,
+        current: &WaveFunction,
+        new: &WaveFunction,
+    ) -> bool {
+        let condition = match self {
+            ChunkMerging::Mixed => inside_chunk || current.count_bits() > 1,
+            ChunkMerging::Interior => inside_chunk,
+            ChunkMerging::Full => true,
+        };
+        condition || new.count_bits() == 0
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ChunkType {
     NonDeterministic { center: IVec2 },
@@ -70,37 +127,131 @@ pub enum ChunkType {
     Center,
 }
 
+impl ChunkType {
+    /// Role of a chunk in `GenerationMode::Deterministic`, corners are at even coordinates,
+    /// centers at odd coordinates and edges in between
+    pub fn from_coordinates(chunk: IVec2) -> Self {
+        match (chunk.x.rem_euclid(2), chunk.y.rem_euclid(2)) {
+            (0, 0) => ChunkType::Corner,
+            (1, 1) => ChunkType::Center,
+            _ => ChunkType::Edge,
+        }
+    }
+
+    /// Chunks that have to be generated before this chunk in `GenerationMode::Deterministic`
+    pub fn dependencies(&self, chunk: IVec2) -> Vec<IVec2> {
+        match self {
+            ChunkType::NonDeterministic { .. } | ChunkType::Corner => Vec::new(),
+            ChunkType::Edge => {
+                let axis = if chunk.x.rem_euclid(2) == 1 {
+                    IVec2::X
+                } else {
+                    IVec2::Y
+                };
+                vec![chunk - axis, chunk + axis]
+            }
+            ChunkType::Center => {
+                let mut dependencies = Vec::new();
+                for y in -1..=1 {
+                    for x in -1..=1 {
+                        if x != 0 || y != 0 {
+                            dependencies.push(chunk + IVec2::new(x, y));
+                        }
+                    }
+                }
+                dependencies
+            }
+        }
+    }
+}
+
+/// Derives the seed of a chunk from the world seed, the result only depends on the coordinates of
+/// the chunk and how often it was attempted so it is the same regardless of the order chunks are
+/// generated in
+pub fn chunk_seed(seed: u64, chunk: IVec2, attempt: u32) -> u64 {
+    hash_seed(seed, &[chunk.x as u32, chunk.y as u32, attempt])
+}
+
+pub(crate) fn hash_seed(seed: u64, values: &[u32]) -> u64 {
+    // splitmix64, stable across platforms and rust versions unlike the std hasher
+    let mut hash = seed;
+    for &value in values {
+        hash = hash
+            .wrapping_add(value as u64)
+            .wrapping_add(0x9e3779b97f4a7c15);
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+        hash ^= hash >> 31;
+    }
+    hash
+}
+
+/// Every tile that is rendered as `render_tile`, this is usually a single tile but for overlapping
+/// tilesets it is every pattern with the same color, like "any water tile"
+pub fn tiles_rendered_as(tileset: &dyn TileSet, render_tile: usize) -> WaveFunction {
+    let mut tiles = WaveFunction::empty();
+    for tile in 0..tileset.tile_count() {
+        if tileset.get_render_tile(tile) == render_tile {
+            tiles.add_tile(tile);
+        }
+    }
+    tiles
+}
+
 impl World {
-    pub fn extract_chunk(&self, chunk: IVec2) -> Graph<WaveFunction> {
-        let (bottom_left, top_right) = self.chunk_bounds(
-            chunk,
-            self.chunk_settings.overlap + self.chunk_settings.discard,
-        );
+    /// Unconstrained world with the size, diagonals, borders and periodicity of `settings`, cells
+    /// can be painted with `World::paint` before generating
+    pub fn new(
+        tileset: Arc<dyn TileSet>,
+        settings: &GridGraphSettings,
+        chunk_settings: ChunkSettings,
+        wfc_settings: WfcSettings,
+        seed: u64,
+    ) -> Self {
+        let filled = WaveFunction::filled(tileset.tile_count());
+        Self {
+            world: vec![vec![filled; settings.height]; settings.width],
+            generated_chunks: HashMap::new(),
+            chunk_settings,
+            tileset,
+            painted: HashMap::new(),
+            diagonals: settings.diagonals,
+            borders: settings.borders.clone(),
+            periodic: settings.periodic,
+            seed,
+            rng: SmallRng::seed_from_u64(seed),
+            outstanding: 0,
+            settings: wfc_settings,
+            update_channel: None,
+        }
+    }
+
+    /// Extracts a chunk with the overlap and `discard` around it, the discard is usually
+    /// `chunk_settings.discard` but is enlarged when retrying chunks
+    pub fn extract_chunk(&self, chunk: IVec2, discard: usize) -> Graph<WaveFunction> {
+        let (bottom_left, top_right) =
+            self.chunk_bounds(chunk, self.chunk_settings.overlap + discard);
         let size = top_right - bottom_left;
 
-        let settings = GridGraphSettings {
-            width: size.x as usize,
-            height: size.y as usize,
-            periodic: false,
-        };
-        let filled = WaveFunction::filled(self.tileset.tile_count());
-        let mut graph = grid_graph::create(&settings, filled);
+        let mut graph = self.create_graph(size);
 
         let chunk_bottom_left = chunk * self.chunk_settings.size as i32;
         let chunk_top_right = (chunk + IVec2::ONE) * self.chunk_settings.size as i32;
+        let merging = self.chunk_settings.merging;
         for y in 0..size.y {
             for x in 0..size.x {
                 let pos = IVec2::new(bottom_left.x + x, bottom_left.y + y);
-                if pos.cmplt(chunk_bottom_left).any()
-                    || pos.cmpge(chunk_top_right).any()
-                    || self.chunk_settings.merging == ChunkMerging::Interior
-                    || self.chunk_settings.merging == ChunkMerging::Full
-                {
+                let inside = pos.cmpge(chunk_bottom_left).all() && pos.cmplt(chunk_top_right).all();
+                if merging.should_extract(inside) {
+                    let pos = self.wrap(pos);
                     let tile = &self.world[pos.x as usize][pos.y as usize];
                     graph.tiles[y as usize * size.x as usize + x as usize] = tile.clone();
                 }
             }
         }
+        self.apply_borders(&mut graph, bottom_left, size);
+        self.apply_ground(&mut graph, bottom_left, size);
+        self.apply_painting(&mut graph, bottom_left, size);
 
         // for y in (0..size.y).rev() {
         //     for x in 0..size.x {
@@ -116,15 +267,15 @@ impl World {
         graph
     }
 
-    pub fn merge_chunk(&mut self, chunk: IVec2, graph: Graph<WaveFunction>) {
-        let (bottom_left, top_right) = self.chunk_bounds(
-            chunk,
-            self.chunk_settings.overlap + self.chunk_settings.discard,
-        );
+    /// Merges a chunk extracted with the same `discard` back into the world
+    pub fn merge_chunk(&mut self, chunk: IVec2, discard: usize, graph: Graph<WaveFunction>) {
+        let (bottom_left, top_right) =
+            self.chunk_bounds(chunk, self.chunk_settings.overlap + discard);
         let size = top_right - bottom_left;
 
         let chunk_bottom_left = chunk * self.chunk_settings.size as i32;
         let chunk_top_right = (chunk + IVec2::ONE) * self.chunk_settings.size as i32;
+        let merging = self.chunk_settings.merging;
 
         // Note: Assumes that the graph is a grid graph with a standard ordering
         for x in 0..size.x {
@@ -137,43 +288,384 @@ impl World {
                     continue;
                 }
 
-                // overwrite tiles inside the chunk while preserving tiles on the border
                 let tile = graph.tiles[y as usize * size.x as usize + x as usize].clone();
-                let condition = match self.chunk_settings.merging {
-                    ChunkMerging::Mixed => {
-                        (pos.cmpge(chunk_bottom_left).all() && pos.cmplt(chunk_top_right).all())
-                            || self.world[pos.x as usize][pos.y as usize].count_bits() > 1
-                    }
-                    ChunkMerging::Interior => {
-                        pos.cmpge(chunk_bottom_left).all() && pos.cmplt(chunk_top_right).all()
-                    }
-                    ChunkMerging::Full => true,
-                };
-                if condition || tile.count_bits() == 0 {
+                let inside = pos.cmpge(chunk_bottom_left).all() && pos.cmplt(chunk_top_right).all();
+                let pos = self.wrap(pos);
+                let current = &self.world[pos.x as usize][pos.y as usize];
+                if merging.should_merge(inside, current, &tile) {
                     self.world[pos.x as usize][pos.y as usize] = tile;
                 }
             }
         }
     }
 
+    /// Generates the cells from `bottom_left` to `top_right` again along with `halo` cells around
+    /// them, the cells around those stay as they are and constrain the new cells to fit in
+    ///
+    /// Blocks until the region is solved, the world is left untouched if it fails. Tile counts
+    /// still hold for the whole world, the cells outside of the region use up part of them
+    pub fn regenerate_region(
+        &mut self,
+        bottom_left: IVec2,
+        top_right: IVec2,
+        halo: usize,
+        seed: u64,
+    ) -> Result<()> {
+        if (top_right - bottom_left).cmple(IVec2::ZERO).any() {
+            return Err(anyhow!("Region is empty"));
+        }
+        if self.settings.path.is_some() {
+            return Err(anyhow!(
+                "The path constraint is not supported when regenerating a region"
+            ));
+        }
+
+        let halo = IVec2::splat(halo as i32);
+        let mut reset_bottom_left = bottom_left - halo;
+        let mut reset_top_right = top_right + halo;
+        // the ring of cells around the reset cells is kept to propagate from
+        let mut graph_bottom_left = reset_bottom_left - IVec2::ONE;
+        let mut graph_top_right = reset_top_right + IVec2::ONE;
+        if !self.periodic {
+            reset_bottom_left = reset_bottom_left.max(IVec2::ZERO);
+            reset_top_right = reset_top_right.min(self.size());
+            graph_bottom_left = graph_bottom_left.max(IVec2::ZERO);
+            graph_top_right = graph_top_right.min(self.size());
+        } else if (graph_top_right - graph_bottom_left)
+            .cmpgt(self.size())
+            .any()
+        {
+            return Err(anyhow!("Region is larger than the periodic world"));
+        }
+        let size = graph_top_right - graph_bottom_left;
+
+        let reset =
+            |pos: IVec2| pos.cmpge(reset_bottom_left).all() && pos.cmplt(reset_top_right).all();
+        let mut graph = self.create_graph(size);
+        let mut reset_nodes = Vec::new();
+        let mut reset_cells = vec![vec![false; self.size().y as usize]; self.size().x as usize];
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let index = y as usize * size.x as usize + x as usize;
+                let pos = graph_bottom_left + IVec2::new(x, y);
+                let wrapped = self.wrap(pos);
+                if reset(pos) {
+                    reset_nodes.push(index);
+                    reset_cells[wrapped.x as usize][wrapped.y as usize] = true;
+                } else {
+                    graph.tiles[index] = self.world[wrapped.x as usize][wrapped.y as usize].clone();
+                }
+            }
+        }
+        let counts = self.region_counts(&reset_cells, reset_nodes)?;
+        self.apply_borders(&mut graph, graph_bottom_left, size);
+        self.apply_ground(&mut graph, graph_bottom_left, size);
+        self.apply_painting(&mut graph, graph_bottom_left, size);
+
+        let mut task = WfcTask {
+            graph,
+            tileset: self.tileset.clone(),
+            seed,
+            metadata: None,
+            settings: WfcSettings {
+                progress_updates: None,
+                counts,
+                ..self.settings.clone()
+            },
+            update_channel: None,
+        };
+        SingleThreaded::execute(&mut task).map_err(|e| {
+            match e.downcast_ref::<InvalidInitialState>() {
+                Some(&InvalidInitialState { node }) => {
+                    let node = IVec2::new(node as i32 % size.x, node as i32 / size.x);
+                    anyhow!(
+                        "Invalid initial state at cell {}",
+                        self.wrap(graph_bottom_left + node)
+                    )
+                }
+                None => e,
+            }
+        })?;
+
+        // Note: Assumes that the graph is a grid graph with a standard ordering
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let pos = graph_bottom_left + IVec2::new(x, y);
+                if reset(pos) {
+                    let pos = self.wrap(pos);
+                    self.world[pos.x as usize][pos.y as usize] =
+                        task.graph.tiles[y as usize * size.x as usize + x as usize].clone();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The tile counts of `settings` for regenerating the cells marked in `reset_cells`, which are
+    /// `nodes` in the graph of the region, the cells that are kept use up part of every count
+    fn region_counts(
+        &self,
+        reset_cells: &[Vec<bool>],
+        nodes: Vec<usize>,
+    ) -> Result<Vec<TileCount>> {
+        let mut counts = Vec::new();
+        for count in self.settings.counts.iter() {
+            let kept = self
+                .world
+                .iter()
+                .flatten()
+                .zip(reset_cells.iter().flatten())
+                .filter(|&(tile, &reset)| {
+                    !reset
+                        && tile
+                            .collapse()
+                            .is_some_and(|tile| count.tiles.contains(&tile))
+                })
+                .count();
+            let max = match count.max {
+                usize::MAX => usize::MAX,
+                max => max.checked_sub(kept).ok_or_else(|| {
+                    anyhow!(
+                        "Cells outside of the region already use more than {} of the tiles {:?}",
+                        max,
+                        count.tiles
+                    )
+                })?,
+            };
+            counts.push(TileCount {
+                tiles: count.tiles.clone(),
+                min: count.min.saturating_sub(kept),
+                max,
+                nodes: nodes.clone(),
+            });
+        }
+        Ok(counts)
+    }
+
+    /// Splits the tile counts of `settings` between the chunks, every chunk gets its share of the
+    /// minimum and maximum and only counts the cells inside of it, so the counts also hold for the
+    /// whole world as long as no chunk fails
+    pub fn chunk_counts(&self, chunk: IVec2, discard: usize) -> Vec<TileCount> {
+        let chunks = self.chunk_count();
+        let total = (chunks.x * chunks.y) as usize;
+        // which chunks get the remainders depends on the seed
+        let index =
+            ((chunk.y * chunks.x + chunk.x) as usize + (self.seed % total as u64) as usize) % total;
+
+        let (bottom_left, top_right) =
+            self.chunk_bounds(chunk, self.chunk_settings.overlap + discard);
+        let width = top_right.x - bottom_left.x;
+        let (chunk_bottom_left, chunk_top_right) = self.chunk_bounds(chunk, 0);
+        let mut nodes = Vec::new();
+        for y in chunk_bottom_left.y..chunk_top_right.y {
+            for x in chunk_bottom_left.x..chunk_top_right.x {
+                let local = IVec2::new(x, y) - bottom_left;
+                nodes.push((local.y * width + local.x) as usize);
+            }
+        }
+
+        self.settings
+            .counts
+            .iter()
+            .map(|count| count.share(index, total, nodes.clone()))
+            .collect()
+    }
+
+    /// Restricts a cell to `tiles` in every chunk generated from now on, painting a cell again
+    /// replaces its previous tiles
+    pub fn paint(&mut self, pos: IVec2, tiles: WaveFunction) {
+        self.painted.insert(pos, tiles);
+    }
+
+    pub fn erase(&mut self, pos: IVec2) {
+        self.painted.remove(&pos);
+    }
+
+    /// Restricts the painted cells of a grid graph covering `size` cells from `bottom_left`
+    pub fn apply_painting(&self, graph: &mut Graph<WaveFunction>, bottom_left: IVec2, size: IVec2) {
+        if self.painted.is_empty() {
+            return;
+        }
+
+        // Note: Assumes that the graph is a grid graph with a standard ordering
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let pos = self.wrap(bottom_left + IVec2::new(x, y));
+                if let Some(tiles) = self.painted.get(&pos) {
+                    let index = y as usize * size.x as usize + x as usize;
+                    graph.tiles[index] = WaveFunction::intersect(&graph.tiles[index], tiles);
+                }
+            }
+        }
+    }
+
+    /// Restricts the cells of a grid graph covering `size` cells from `bottom_left` that lie along
+    /// the edges of the world
+    pub fn apply_borders(&self, graph: &mut Graph<WaveFunction>, bottom_left: IVec2, size: IVec2) {
+        // periodic worlds have no edges
+        if self.periodic {
+            return;
+        }
+        let tileset = self.tileset.as_ref();
+        self.borders
+            .apply(tileset, graph, bottom_left, size, self.size());
+    }
+
+    /// Fixes the cells of a grid graph covering `size` cells from `bottom_left` that lie in the
+    /// bottom row of the world to the ground pattern, see `OverlappingTileset::constrain_ground`
+    pub fn apply_ground(&self, graph: &mut Graph<WaveFunction>, bottom_left: IVec2, size: IVec2) {
+        let tileset = self.tileset.as_any().downcast_ref::<OverlappingTileset>();
+        let ground = match tileset.and_then(|tileset| tileset.get_ground()) {
+            Some(ground) => ground,
+            None => return,
+        };
+
+        // Note: Assumes that the graph is a grid graph with a standard ordering
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let index = y as usize * size.x as usize + x as usize;
+                let tile = &mut graph.tiles[index];
+                if self.wrap(bottom_left + IVec2::new(x, y)).y == 0 {
+                    *tile = WaveFunction::intersect(tile, &WaveFunction::single(ground));
+                } else {
+                    tile.remove_tile(ground);
+                }
+            }
+        }
+    }
+
+    /// Position in the world of a node of a chunk extracted with `discard`
+    pub fn chunk_node_position(&self, chunk: IVec2, discard: usize, node: usize) -> IVec2 {
+        let (bottom_left, top_right) =
+            self.chunk_bounds(chunk, self.chunk_settings.overlap + discard);
+        let width = (top_right.x - bottom_left.x) as usize;
+        self.wrap(bottom_left + IVec2::new((node % width) as i32, (node / width) as i32))
+    }
+
+    /// Resets the cells of a chunk to be unconstrained and forgets that it was generated
+    pub fn clear_chunk(&mut self, chunk: IVec2) {
+        let (bottom_left, top_right) = self.chunk_bounds(chunk, 0);
+        let filled = WaveFunction::filled(self.tileset.tile_count());
+        for x in bottom_left.x..top_right.x {
+            for y in bottom_left.y..top_right.y {
+                self.world[x as usize][y as usize] = filled.clone();
+            }
+        }
+        self.generated_chunks.remove(&chunk);
+    }
+
+    pub fn size(&self) -> IVec2 {
+        IVec2::new(self.world.len() as i32, self.world[0].len() as i32)
+    }
+
+    /// Position of a cell inside of the world, positions past the edges of periodic worlds wrap
+    /// around to the other side
+    pub fn wrap(&self, pos: IVec2) -> IVec2 {
+        match self.periodic {
+            true => IVec2::new(
+                pos.x.rem_euclid(self.size().x),
+                pos.y.rem_euclid(self.size().y),
+            ),
+            false => pos,
+        }
+    }
+
+    /// Coordinates of a chunk inside of the world, wraps around in periodic worlds and is `None`
+    /// past the edges of other worlds
+    pub fn wrap_chunk(&self, chunk: IVec2) -> Option<IVec2> {
+        let chunks = self.chunk_count();
+        if self.periodic {
+            Some(IVec2::new(
+                chunk.x.rem_euclid(chunks.x),
+                chunk.y.rem_euclid(chunks.y),
+            ))
+        } else if chunk.cmpge(IVec2::ZERO).all() && chunk.cmplt(chunks).all() {
+            Some(chunk)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the reason chunks of the world can't wrap around, chunks have to fit the world
+    /// exactly and extracting them must not reach the same cell twice
+    ///
+    /// Only the deterministic schedule is supported, chunks of the non-deterministic schedule meet
+    /// on the far side of the world and read overlap written by chunks generated at the same time
+    pub fn check_periodic(&self, generation_mode: GenerationMode) -> Result<()> {
+        if generation_mode != GenerationMode::Deterministic {
+            return Err(anyhow!("Periodic worlds need deterministic generation"));
+        }
+
+        let size = self.size();
+        let chunk_size = self.chunk_settings.size as i32;
+        if size.x % chunk_size != 0 || size.y % chunk_size != 0 {
+            return Err(anyhow!(
+                "Periodic worlds need a size that is a multiple of the chunk size"
+            ));
+        }
+
+        // corners and edges have to alternate across the edges of the world as well
+        let chunks = self.chunk_count();
+        if chunks.x % 2 == 1 || chunks.y % 2 == 1 {
+            return Err(anyhow!(
+                "Periodic worlds need an even number of chunks along each axis"
+            ));
+        }
+
+        let recovery = self.chunk_settings.recovery;
+        let discard =
+            self.chunk_settings.discard + recovery.enlarge_discard * recovery.retries as usize;
+        let window = chunk_size + 2 * (self.chunk_settings.overlap + discard) as i32;
+        if window > size.min_element() {
+            return Err(anyhow!(
+                "Chunks with their overlap and discard are larger than the periodic world"
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Unconstrained grid graph of `size` cells connected the same way as the world
+    fn create_graph(&self, size: IVec2) -> Graph<WaveFunction> {
+        let settings = GridGraphSettings {
+            width: size.x as usize,
+            height: size.y as usize,
+            periodic: false,
+            diagonals: self.diagonals,
+            borders: Borders::default(),
+        };
+        let filled = WaveFunction::filled(self.tileset.tile_count());
+        match self.tileset.as_any().downcast_ref::<OverlappingTileset>() {
+            Some(tileset) => tileset.create_graph(&settings, filled),
+            None => grid_graph::create(&settings, filled),
+        }
+    }
+
     pub fn chunk_bounds(&self, pos: IVec2, overlap: usize) -> (IVec2, IVec2) {
-        let world_size = IVec2::new(self.world.len() as i32, self.world[0].len() as i32);
-        let bottom_left =
-            (pos * self.chunk_settings.size as i32 - IVec2::splat(overlap as i32)).max(IVec2::ZERO);
-        let top_right = ((pos + IVec2::ONE) * self.chunk_settings.size as i32
-            + IVec2::splat(overlap as i32))
-        .min(world_size);
-        (bottom_left, top_right)
+        let bottom_left = pos * self.chunk_settings.size as i32 - IVec2::splat(overlap as i32);
+        let top_right =
+            (pos + IVec2::ONE) * self.chunk_settings.size as i32 + IVec2::splat(overlap as i32);
+        // bounds of periodic worlds reach past the edges, cells there are wrapped with `wrap`
+        match self.periodic {
+            true => (bottom_left, top_right),
+            false => (bottom_left.max(IVec2::ZERO), top_right.min(self.size())),
+        }
+    }
+
+    /// Number of chunks along each axis
+    pub fn chunk_count(&self) -> IVec2 {
+        IVec2::new(
+            self.world.len() as i32 / self.chunk_settings.size as i32,
+            self.world[0].len() as i32 / self.chunk_settings.size as i32,
+        )
     }
 
     pub fn start_generation(&mut self, generation_mode: GenerationMode) -> Vec<(IVec2, ChunkType)> {
         let mut start_chunks = Vec::new();
         match generation_mode {
             GenerationMode::NonDeterministic => {
-                let chunks = IVec2::new(
-                    self.world.len() as i32 / self.chunk_settings.size as i32,
-                    self.world[0].len() as i32 / self.chunk_settings.size as i32,
-                );
+                let chunks = self.chunk_count();
                 let start_chunk = IVec2::new(
                     self.rng.gen_range(0..chunks.x),
                     self.rng.gen_range(0..chunks.y),
@@ -187,10 +679,7 @@ impl World {
                 ));
             }
             GenerationMode::Deterministic => {
-                let chunks = IVec2::new(
-                    self.world.len() as i32 / self.chunk_settings.size as i32,
-                    self.world[0].len() as i32 / self.chunk_settings.size as i32,
-                );
+                let chunks = self.chunk_count();
                 let half_chunks = chunks / 2;
                 for x in 0..half_chunks.x {
                     for y in 0..half_chunks.y {
@@ -215,19 +704,20 @@ impl World {
         match chunk_type {
             ChunkType::NonDeterministic { center } => {
                 'outer: for direction in 0..4 {
-                    let neighbor = chunk + Direction::from(direction).to_ivec2();
-                    let chunks = IVec2::new(
-                        self.world.len() as i32 / self.chunk_settings.size as i32,
-                        self.world[0].len() as i32 / self.chunk_settings.size as i32,
-                    );
-                    if !self.generated_chunks.contains_key(&neighbor)
-                        && neighbor.cmpge(IVec2::ZERO).all()
-                        && neighbor.cmplt(chunks).all()
-                    {
+                    let Some(neighbor) =
+                        self.wrap_chunk(chunk + Direction::from(direction).to_ivec2())
+                    else {
+                        continue;
+                    };
+                    if !self.generated_chunks.contains_key(&neighbor) {
                         // check if neighbor's neighbors are done
                         let mut done = 0;
                         for direction in 0..4 {
-                            let next_neighbor = neighbor + Direction::from(direction).to_ivec2();
+                            let Some(next_neighbor) =
+                                self.wrap_chunk(neighbor + Direction::from(direction).to_ivec2())
+                            else {
+                                continue;
+                            };
                             if let Some(state) = self.generated_chunks.get(&next_neighbor) {
                                 if *state == ChunkState::Done {
                                     done += 1;
@@ -244,24 +734,20 @@ impl World {
                 }
             }
             ChunkType::Corner => {
-                let chunks = IVec2::new(
-                    self.world.len() as i32 / self.chunk_settings.size as i32,
-                    self.world[0].len() as i32 / self.chunk_settings.size as i32,
-                );
-
                 for direction in 0..4 {
-                    let next_corner = chunk + 2 * Direction::from(direction).to_ivec2();
-                    let edge = chunk + Direction::from(direction).to_ivec2();
+                    let next_corner =
+                        self.wrap_chunk(chunk + 2 * Direction::from(direction).to_ivec2());
+                    // check if edge is in bounds
+                    let Some(edge) = self.wrap_chunk(chunk + Direction::from(direction).to_ivec2())
+                    else {
+                        continue;
+                    };
 
                     // check if next corner is in bounds
-                    if next_corner.cmplt(IVec2::ZERO).any() || next_corner.cmpge(chunks).any() {
-                        // check if edge is in bounds
-                        if edge.cmplt(IVec2::ZERO).any() || edge.cmpge(chunks).any() {
-                            continue;
-                        }
+                    let Some(next_corner) = next_corner else {
                         ready_chunks.push((edge, ChunkType::Edge));
                         continue;
-                    }
+                    };
 
                     // check if next corner is done
                     if let Some(state) = self.generated_chunks.get(&next_corner) {
@@ -272,33 +758,27 @@ impl World {
                 }
             }
             ChunkType::Edge => {
-                let chunks = IVec2::new(
-                    self.world.len() as i32 / self.chunk_settings.size as i32,
-                    self.world[0].len() as i32 / self.chunk_settings.size as i32,
-                );
-
                 for direction in 0..4 {
-                    let center = chunk + Direction::from(direction).to_ivec2();
-                    if self.generated_chunks.contains_key(&center)
-                        || center.cmplt(IVec2::ZERO).any()
-                        || center.cmpge(chunks).any()
-                    {
+                    let Some(center) =
+                        self.wrap_chunk(chunk + Direction::from(direction).to_ivec2())
+                    else {
+                        continue;
+                    };
+                    if self.generated_chunks.contains_key(&center) {
                         continue;
                     }
 
                     let mut good = 0;
                     for direction in 0..4 {
-                        let edge = center + Direction::from(direction).to_ivec2();
-                        if let Some(state) = self.generated_chunks.get(&edge) {
-                            if *state == ChunkState::Done {
-                                good += 1;
-                                continue;
-                            }
-                        }
-
-                        if edge.cmplt(IVec2::ZERO).any() || edge.cmpge(chunks).any() {
+                        let Some(edge) =
+                            self.wrap_chunk(center + Direction::from(direction).to_ivec2())
+                        else {
+                            // out of bounds
                             good += 1;
                             continue;
+                        };
+                        if self.generated_chunks.get(&edge) == Some(&ChunkState::Done) {
+                            good += 1;
                         }
                     }
 
@@ -0,0 +1,312 @@
+use crate::{
+    grid3d_graph::{self, GridGraph3dSettings},
+    vox,
+    world::{hash_seed, ChunkSettings, ChunkState},
+};
+use anyhow::{anyhow, Result};
+use bevy::{prelude::*, utils::HashMap};
+use core_wfc::{
+    wfc_backend::Backend, wfc_task::WfcSettings, Graph, TileRender, TileSet, WaveFunction, WfcTask,
+};
+use std::{path::Path, sync::Arc};
+
+/// Role of a chunk in the deterministic schedule, decided by how many of its coordinates are odd
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ChunkType3d {
+    Corner,
+    Edge,
+    Face,
+    Center,
+}
+
+impl ChunkType3d {
+    pub fn from_coordinates(chunk: IVec3) -> Self {
+        let odd = chunk
+            .to_array()
+            .iter()
+            .filter(|c| c.rem_euclid(2) == 1)
+            .count();
+        match odd {
+            0 => ChunkType3d::Corner,
+            1 => ChunkType3d::Edge,
+            2 => ChunkType3d::Face,
+            _ => ChunkType3d::Center,
+        }
+    }
+
+    /// Chunks that have to be generated before `chunk`, these are all neighbors that only differ
+    /// along the odd axes of the chunk so they have fewer odd coordinates
+    pub fn dependencies(chunk: IVec3) -> Vec<IVec3> {
+        let odd = chunk.to_array().map(|c| c.rem_euclid(2) == 1);
+        let range = |axis: usize| if odd[axis] { -1..=1 } else { 0..=0 };
+
+        let mut dependencies = Vec::new();
+        for z in range(2) {
+            for y in range(1) {
+                for x in range(0) {
+                    if x != 0 || y != 0 || z != 0 {
+                        dependencies.push(chunk + IVec3::new(x, y, z));
+                    }
+                }
+            }
+        }
+        dependencies
+    }
+}
+
+/// Metadata of every task queued by a `World3d`
+pub struct Chunk3dTaskData {
+    pub chunk: IVec3,
+}
+
+/// Volume generated in cubic chunks with the deterministic corner, edge, face and center schedule,
+/// the 3D counterpart of `World`
+///
+/// Chunks don't recover from failures, `ChunkSettings::recovery` is ignored
+pub struct World3d {
+    /// Cells indexed as `world[x][y][z]`
+    pub world: Vec<Vec<Vec<WaveFunction>>>,
+    pub generated_chunks: HashMap<IVec3, ChunkState>,
+    pub chunk_settings: ChunkSettings,
+    pub tileset: Arc<dyn TileSet>,
+    pub seed: u64,
+    pub outstanding: usize,
+    pub settings: WfcSettings,
+}
+
+impl World3d {
+    pub fn new(
+        size: IVec3,
+        tileset: Arc<dyn TileSet>,
+        chunk_settings: ChunkSettings,
+        settings: WfcSettings,
+        seed: u64,
+    ) -> Self {
+        let filled = WaveFunction::filled(tileset.tile_count());
+        Self {
+            world: vec![vec![vec![filled; size.z as usize]; size.y as usize]; size.x as usize],
+            generated_chunks: HashMap::new(),
+            chunk_settings,
+            tileset,
+            seed,
+            outstanding: 0,
+            settings,
+        }
+    }
+
+    pub fn size(&self) -> IVec3 {
+        IVec3::new(
+            self.world.len() as i32,
+            self.world[0].len() as i32,
+            self.world[0][0].len() as i32,
+        )
+    }
+
+    /// Number of chunks along each axis
+    pub fn chunk_count(&self) -> IVec3 {
+        let chunk_size = self.chunk_settings.size as i32;
+        (self.size() + IVec3::splat(chunk_size - 1)) / chunk_size
+    }
+
+    pub fn chunk_bounds(&self, chunk: IVec3, overlap: usize) -> (IVec3, IVec3) {
+        let chunk_size = self.chunk_settings.size as i32;
+        let bottom_left = (chunk * chunk_size - IVec3::splat(overlap as i32)).max(IVec3::ZERO);
+        let top_right =
+            ((chunk + IVec3::ONE) * chunk_size + IVec3::splat(overlap as i32)).min(self.size());
+        (bottom_left, top_right)
+    }
+
+    /// Generates every chunk and blocks until they are done, stops at the first failed chunk
+    pub fn generate(&mut self, backend: &mut dyn Backend) -> Result<()> {
+        let chunks = self.chunk_count();
+        for x in (0..chunks.x).step_by(2) {
+            for y in (0..chunks.y).step_by(2) {
+                for z in (0..chunks.z).step_by(2) {
+                    self.queue_chunk(backend, IVec3::new(x, y, z))?;
+                }
+            }
+        }
+
+        let mut failed = false;
+        while self.outstanding > 0 {
+            let (task, result) = backend.wait_for_output();
+            self.outstanding -= 1;
+
+            // chunks that were still generating when another chunk failed are ignored
+            if failed {
+                continue;
+            }
+
+            let &Chunk3dTaskData { chunk } = task
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.downcast_ref())
+                .expect("Task was not queued by a World3d");
+            self.merge_chunk(chunk, task.graph);
+
+            if let Err(e) = result {
+                error!("Failed to generate chunk {:?}: {:?}", chunk, e);
+                self.generated_chunks.insert(chunk, ChunkState::Failed);
+                failed = true;
+                continue;
+            }
+
+            self.generated_chunks.insert(chunk, ChunkState::Done);
+            for ready in self.process_chunk(chunk) {
+                self.queue_chunk(backend, ready)?;
+            }
+        }
+
+        match failed {
+            true => Err(anyhow!("Failed to generate world")),
+            false => Ok(()),
+        }
+    }
+
+    /// Returns the chunks that can be generated now that `chunk` is done
+    pub fn process_chunk(&self, chunk: IVec3) -> Vec<IVec3> {
+        let chunks = self.chunk_count();
+        let in_bounds = |chunk: &IVec3| chunk.cmpge(IVec3::ZERO).all() && chunk.cmplt(chunks).all();
+
+        let mut ready_chunks = Vec::new();
+        for z in -1..=1 {
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    let next = chunk + IVec3::new(x, y, z);
+                    if !in_bounds(&next) || self.generated_chunks.contains_key(&next) {
+                        continue;
+                    }
+
+                    let dependencies = ChunkType3d::dependencies(next);
+                    if dependencies.contains(&chunk)
+                        && dependencies
+                            .iter()
+                            .filter(|d| in_bounds(d))
+                            .all(|d| self.generated_chunks.get(d) == Some(&ChunkState::Done))
+                    {
+                        ready_chunks.push(next);
+                    }
+                }
+            }
+        }
+        ready_chunks
+    }
+
+    pub fn extract_chunk(&self, chunk: IVec3) -> Graph<WaveFunction> {
+        let (bottom_left, top_right) = self.chunk_bounds(
+            chunk,
+            self.chunk_settings.overlap + self.chunk_settings.discard,
+        );
+        let size = top_right - bottom_left;
+
+        let settings = GridGraph3dSettings {
+            width: size.x as usize,
+            height: size.y as usize,
+            depth: size.z as usize,
+            periodic: BVec3::FALSE,
+        };
+        let filled = WaveFunction::filled(self.tileset.tile_count());
+        let mut graph = grid3d_graph::create(&settings, filled);
+
+        let (chunk_bottom_left, chunk_top_right) = self.chunk_bounds(chunk, 0);
+        let merging = self.chunk_settings.merging;
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let pos = bottom_left + IVec3::new(x, y, z);
+                    let inside =
+                        pos.cmpge(chunk_bottom_left).all() && pos.cmplt(chunk_top_right).all();
+                    if merging.should_extract(inside) {
+                        graph.tiles[settings.index(IVec3::new(x, y, z))] =
+                            self.world[pos.x as usize][pos.y as usize][pos.z as usize].clone();
+                    }
+                }
+            }
+        }
+
+        graph
+    }
+
+    pub fn merge_chunk(&mut self, chunk: IVec3, graph: Graph<WaveFunction>) {
+        let (bottom_left, top_right) = self.chunk_bounds(
+            chunk,
+            self.chunk_settings.overlap + self.chunk_settings.discard,
+        );
+        let size = top_right - bottom_left;
+        let settings = GridGraph3dSettings {
+            width: size.x as usize,
+            height: size.y as usize,
+            depth: size.z as usize,
+            periodic: BVec3::FALSE,
+        };
+
+        let (chunk_bottom_left, chunk_top_right) = self.chunk_bounds(chunk, 0);
+        let (merge_bottom_left, merge_top_right) =
+            self.chunk_bounds(chunk, self.chunk_settings.overlap);
+        let merging = self.chunk_settings.merging;
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let pos = bottom_left + IVec3::new(x, y, z);
+                    if pos.cmplt(merge_bottom_left).any() || pos.cmpge(merge_top_right).any() {
+                        continue;
+                    }
+
+                    let tile = graph.tiles[settings.index(IVec3::new(x, y, z))].clone();
+                    let inside =
+                        pos.cmpge(chunk_bottom_left).all() && pos.cmplt(chunk_top_right).all();
+                    let current = &mut self.world[pos.x as usize][pos.y as usize][pos.z as usize];
+                    if merging.should_merge(inside, current, &tile) {
+                        *current = tile;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Saves the volume as a MagicaVoxel file, needs a tileset rendered with colors, transparent
+    /// tiles and cells that are not collapsed are left empty
+    pub fn save_vox(&self, path: &Path) -> Result<()> {
+        let assets = self.tileset.get_render_tile_assets();
+        let size = self.size();
+
+        let mut colors = Vec::with_capacity((size.x * size.y * size.z) as usize);
+        for z in 0..size.z as usize {
+            for y in 0..size.y as usize {
+                for x in 0..size.x as usize {
+                    let tile = match self.world[x][y][z].collapse() {
+                        Some(tile) => self.tileset.get_render_tile(tile),
+                        None => {
+                            colors.push(None);
+                            continue;
+                        }
+                    };
+                    colors.push(match &assets[tile].0 {
+                        TileRender::Color(color) if color.a() > 0.0 => Some(*color),
+                        TileRender::Color(_) => None,
+                        TileRender::Sprite(_) => {
+                            return Err(anyhow!("Tiles rendered as sprites can't be saved as .vox"))
+                        }
+                    });
+                }
+            }
+        }
+
+        vox::save(path, size, &colors)
+    }
+
+    fn queue_chunk(&mut self, backend: &mut dyn Backend, chunk: IVec3) -> Result<()> {
+        self.generated_chunks.insert(chunk, ChunkState::Scheduled);
+        let task = WfcTask {
+            graph: self.extract_chunk(chunk),
+            tileset: self.tileset.clone(),
+            seed: hash_seed(self.seed, &[chunk.x as u32, chunk.y as u32, chunk.z as u32]),
+            metadata: Some(Arc::new(Chunk3dTaskData { chunk })),
+            settings: self.settings.clone(),
+            update_channel: None,
+        };
+
+        self.outstanding += 1;
+        backend.queue_task(task)
+    }
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use core_wfc::{
+    wfc_backend::SingleThreaded,
+    wfc_task::{BacktrackingHeuristic, BacktrackingSettings, WfcSettings},
+    TileSet, WaveFunction, WfcTask,
+};
+use grid_wfc::{grid_graph, mxgmn_tileset::MxgmnTileset};
+use std::sync::Arc;
+
+#[test]
+fn restrictions_survive_backtracking() {
+    let tileset = Arc::new(MxgmnTileset::new(&common::asset("mxgmn/Summer.xml"), None).unwrap());
+    let tile_count = tileset.tile_count();
+    // the left half can't use the first third of the tiles (cliffs and grass)
+    let mut allowed = WaveFunction::empty();
+    for tile in tile_count / 3..tile_count {
+        allowed.add_tile(tile);
+    }
+
+    let settings = common::settings(20);
+    let mut graph = grid_graph::create(&settings, WaveFunction::filled(tile_count));
+    // Note: Assumes that the graph is a grid graph with a standard ordering
+    let restricted = |index: usize| index % 20 < 10;
+    for (index, tiles) in graph.tiles.iter_mut().enumerate() {
+        if restricted(index) {
+            *tiles = allowed.clone();
+        }
+    }
+
+    let mut task = WfcTask {
+        graph,
+        tileset,
+        seed: 9,
+        metadata: None,
+        settings: WfcSettings {
+            backtracking: BacktrackingSettings::Enabled {
+                restarts_left: 1000,
+                heuristic: BacktrackingHeuristic::Restart,
+            },
+            ..Default::default()
+        },
+        update_channel: None,
+    };
+    SingleThreaded::execute(&mut task).unwrap();
+
+    // the seed is picked to restart at least once
+    assert!(matches!(
+        task.settings.backtracking,
+        BacktrackingSettings::Enabled { restarts_left, .. } if restarts_left < 1000
+    ));
+    for (index, tiles) in task.graph.tiles.iter().enumerate() {
+        let tile = tiles.collapse().unwrap();
+        assert!(!restricted(index) || allowed.contains(tile));
+    }
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use bevy::prelude::*;
+use core_wfc::{
+    wfc_backend::SingleThreaded, wfc_task::WfcSettings, TileRender, TileSet, WaveFunction, WfcTask,
+};
+use grid_wfc::{
+    big_tile::{add_big_tiles, BigTile},
+    carcassonne_tileset::CarcassonneTileset,
+    grid_graph::{self, Direction},
+    mxgmn_tileset::MxgmnTileset,
+};
+use std::{collections::BTreeMap, sync::Arc};
+
+#[test]
+fn big_tiles_are_placed_whole() {
+    let small = CarcassonneTileset::default();
+    let grass = common::grass_tile(&small);
+    let tileset = CarcassonneTileset::with_big_tiles(&[BigTile {
+        name: "farm".to_string(),
+        size: UVec2::new(3, 2),
+        weight: 30.0,
+        like: vec![grass],
+        render: TileRender::Color(Color::GREEN),
+    }])
+    .unwrap();
+    assert_eq!(tileset.tile_count(), small.tile_count() + 6);
+    // the footprint is a single choice with the weight of the big tile
+    let weights = tileset.get_weights();
+    assert_eq!(weights[small.tile_count()..].iter().sum::<f32>(), 30.0);
+    // the bottom left part draws the whole tile
+    let (_, transform) = &tileset.get_render_tile_assets()[small.tile_count()];
+    assert_eq!(transform.scale, Vec3::new(3.0, 2.0, 1.0));
+    assert_eq!(transform.translation, Vec3::new(1.0, 0.5, 0.0));
+
+    let settings = common::settings(16);
+    let graph = grid_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+    let mut task = WfcTask {
+        graph,
+        tileset: Arc::new(tileset.clone()),
+        seed: 2,
+        metadata: None,
+        settings: WfcSettings::default(),
+        update_channel: None,
+    };
+    SingleThreaded::execute(&mut task).unwrap();
+
+    // Note: Assumes that the graph is a grid graph with a standard ordering
+    let tile_at = |x: usize, y: usize| task.graph.tiles[y * 16 + x].collapse().unwrap();
+    let farm = tileset.tag_mask("farm");
+    let mut farms = 0;
+    for y in 0..16 {
+        for x in 0..16 {
+            let tile = tile_at(x, y);
+            if !farm.contains(tile) {
+                continue;
+            }
+            let part = tile - small.tile_count();
+            let (part_x, part_y) = (part % 3, part / 3);
+            if part == 0 {
+                farms += 1;
+            }
+            // the rest of the footprint is there around every part
+            for other in 0..6 {
+                let other_x = x + other % 3 - part_x;
+                let other_y = y + other / 3 - part_y;
+                assert_eq!(tile_at(other_x, other_y), small.tile_count() + other);
+            }
+        }
+    }
+    assert!(farms > 0);
+}
+
+#[test]
+fn mxgmn_big_tiles() {
+    let xml = std::fs::read_to_string(common::asset("mxgmn/Summer.xml")).unwrap();
+    let xml = xml.replace(
+        "</set>",
+        r#"<bigtiles><bigtile name="pond" width="2" height="2" like="water_a"/></bigtiles></set>"#,
+    );
+    let path = common::temp_dir("mxgmn_big_tiles").join("BigSummer.xml");
+    std::fs::write(&path, xml).unwrap();
+    let summer = MxgmnTileset::new(&common::asset("mxgmn/Summer.xml"), None).unwrap();
+    let tileset = MxgmnTileset::new(&path, None).unwrap();
+
+    let count = summer.tile_count();
+    assert_eq!(tileset.tile_count(), count + 4);
+    assert_eq!(tileset.tag_mask("pond").count_bits(), 4);
+    let assets = tileset.get_render_tile_assets();
+    assert!(matches!(&assets[count].0, TileRender::Sprite(path) if path.ends_with("pond.png")));
+    assert!(matches!(assets[count + 1].0, TileRender::Color(color) if color.a() == 0.0));
+
+    // outside the pond acts like water, inside only the next part fits
+    let constraints = tileset.get_constraints();
+    let water_a = summer.tag_mask("water_a").collapse().unwrap();
+    let left = 2;
+    assert!(constraints[count][left].contains(water_a));
+    assert!(constraints[water_a][3].contains(count));
+    let mut right_part = WaveFunction::empty();
+    right_part.add_tile(count + 1);
+    assert_eq!(constraints[count][3], right_part);
+}
+
+#[test]
+fn diagonal_constraints_include_the_parts() {
+    // two small tiles that fit everywhere except tile 1 up and right of tile 0
+    let (up_right, down_left) = (Direction::UpRight as usize, Direction::DownLeft as usize);
+    let mut constraints = vec![vec![WaveFunction::filled(2); 8]; 2];
+    constraints[0][up_right].remove_tile(1);
+    constraints[1][down_left].remove_tile(0);
+    let (mut weights, mut render) = (vec![1.0; 2], Vec::new());
+    let (mut tags, mut needs_neighbor) = (BTreeMap::new(), Vec::new());
+    let pond = BigTile {
+        name: "pond".to_string(),
+        size: UVec2::new(2, 2),
+        weight: 1.0,
+        like: vec![0],
+        render: TileRender::Color(Color::BLUE),
+    };
+    add_big_tiles(
+        std::slice::from_ref(&pond),
+        &mut constraints,
+        &mut weights,
+        &mut render,
+        &mut tags,
+        &mut needs_neighbor,
+    )
+    .unwrap();
+
+    let tiles = |tiles: &[usize]| {
+        let mut wave_function = WaveFunction::empty();
+        for &tile in tiles {
+            wave_function.add_tile(tile);
+        }
+        wave_function
+    };
+    assert!(constraints.iter().all(|tile| tile.len() == 8));
+    // parts 2 to 5 from the bottom left, inside the footprint only the opposite corner fits
+    assert_eq!(constraints[2][up_right], tiles(&[5]));
+    assert_eq!(constraints[2][down_left], tiles(&[0, 1, 3, 4, 5]));
+    assert_eq!(constraints[1][up_right], tiles(&[0, 1, 2, 3, 4]));
+    assert_eq!(needs_neighbor[up_right], tiles(&[2]));
+
+    // tables with some tiles missing their diagonals are rejected
+    let mut ragged = vec![
+        vec![WaveFunction::filled(2); 8],
+        vec![WaveFunction::filled(2); 4],
+    ];
+    assert!(add_big_tiles(
+        &[pond],
+        &mut ragged,
+        &mut weights,
+        &mut render,
+        &mut tags,
+        &mut Vec::new(),
+    )
+    .is_err());
+}
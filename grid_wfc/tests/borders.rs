@@ -0,0 +1,98 @@
+mod common;
+
+use bevy::prelude::*;
+use core_wfc::{
+    wfc_backend::{MultiThreaded, SingleThreaded},
+    wfc_task::WfcSettings,
+    TileSet, WaveFunction, WfcTask,
+};
+use grid_wfc::{
+    carcassonne_tileset::CarcassonneTileset,
+    grid_graph::{self, Border, Borders, Direction, GridGraphSettings},
+    mxgmn_tileset::MxgmnTileset,
+    single_shot,
+    world::{ChunkSettings, GenerationMode},
+};
+use std::sync::Arc;
+
+fn settings(size: usize, borders: Borders) -> GridGraphSettings {
+    GridGraphSettings {
+        borders,
+        ..common::settings(size)
+    }
+}
+
+fn on_edge(pos: IVec2, size: i32) -> bool {
+    pos.cmpeq(IVec2::ZERO).any() || pos.cmpeq(IVec2::splat(size - 1)).any()
+}
+
+#[test]
+fn chunked_world_is_surrounded_by_water() {
+    let tileset = MxgmnTileset::new(&common::asset("mxgmn/Summer.xml"), None).unwrap();
+    let water = tileset.tag_mask("water");
+    let borders = Borders::all(Border::Tiles(water.tile_iter().collect()));
+
+    let (world, result) = single_shot::generate_world(
+        Arc::new(tileset),
+        &mut MultiThreaded::new(4),
+        settings(32, borders),
+        5,
+        GenerationMode::Deterministic,
+        ChunkSettings::default(),
+        WfcSettings::default(),
+    );
+    result.unwrap();
+
+    for x in 0..32 {
+        for y in 0..32 {
+            let tile = world.world[x as usize][y as usize].collapse().unwrap();
+            if on_edge(IVec2::new(x, y), 32) {
+                assert!(
+                    water.contains(tile),
+                    "cell {} is not water",
+                    IVec2::new(x, y)
+                );
+            }
+        }
+    }
+    // the island itself is not all water
+    assert!(world
+        .world
+        .iter()
+        .flatten()
+        .any(|tiles| !water.contains(tiles.collapse().unwrap())));
+}
+
+#[test]
+fn edges_fit_the_outside_tile() {
+    let tileset = Arc::new(CarcassonneTileset::default());
+    let grass = common::grass_tile(tileset.as_ref());
+    let settings = settings(12, Borders::all(Border::Outside(vec![grass])));
+    let size = IVec2::splat(12);
+    let mut graph = grid_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+    let borders = &settings.borders;
+    borders.apply(tileset.as_ref(), &mut graph, IVec2::ZERO, size, size);
+
+    let mut task = WfcTask {
+        graph,
+        tileset: tileset.clone(),
+        seed: 2,
+        metadata: None,
+        settings: WfcSettings::default(),
+        update_channel: None,
+    };
+    SingleThreaded::execute(&mut task).unwrap();
+
+    let constraints = tileset.get_constraints();
+    for (index, tiles) in task.graph.tiles.iter().enumerate() {
+        let pos = IVec2::new(index as i32 % 12, index as i32 / 12);
+        let tile = tiles.collapse().unwrap();
+        for direction in 0..4 {
+            let outside = pos + Direction::from(direction).to_ivec2();
+            if outside.cmplt(IVec2::ZERO).any() || outside.cmpge(size).any() {
+                let other = Direction::from(direction).other() as usize;
+                assert!(constraints[grass][other].contains(tile));
+            }
+        }
+    }
+}
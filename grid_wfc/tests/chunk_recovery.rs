@@ -0,0 +1,190 @@
+mod common;
+
+use bevy::{prelude::*, utils::HashMap};
+use core_wfc::{
+    wfc_backend::{Backend, SingleThreaded},
+    wfc_task::WfcSettings,
+    WaveFunction, WfcTask,
+};
+use grid_wfc::{
+    carcassonne_tileset::CarcassonneTileset,
+    chunked_generator::ChunkTaskData,
+    single_shot::generate_painted_world,
+    world::{chunk_seed, ChunkRecovery, ChunkSettings, GenerationMode, World},
+};
+use std::sync::Arc;
+
+const SEED: u64 = 5;
+const LAST: IVec2 = IVec2::new(1, 1);
+
+/// Backend that records the chunk, discard and seed of every queued task and fails the first
+/// results of some chunks
+struct Scripted {
+    backend: SingleThreaded,
+    queued: Vec<(IVec2, usize, u64)>,
+    failures: HashMap<IVec2, u32>,
+}
+
+impl Backend for Scripted {
+    fn queue_task(&mut self, task: WfcTask) -> anyhow::Result<()> {
+        let data = task.metadata.as_ref().unwrap();
+        let data = data.downcast_ref::<ChunkTaskData>().unwrap();
+        self.queued.push((data.chunk, data.discard, task.seed));
+        self.backend.queue_task(task)
+    }
+
+    fn get_output(&mut self) -> Option<(WfcTask, anyhow::Result<()>)> {
+        self.backend.get_output().map(|output| self.script(output))
+    }
+
+    fn wait_for_output(&mut self) -> (WfcTask, anyhow::Result<()>) {
+        let output = self.backend.wait_for_output();
+        self.script(output)
+    }
+
+    fn clear(&mut self) {
+        self.backend.clear()
+    }
+}
+
+impl Scripted {
+    fn new(failures: HashMap<IVec2, u32>) -> Self {
+        Self {
+            backend: SingleThreaded::new(),
+            queued: Vec::new(),
+            failures,
+        }
+    }
+
+    fn script(
+        &mut self,
+        (task, result): (WfcTask, anyhow::Result<()>),
+    ) -> (WfcTask, anyhow::Result<()>) {
+        let data = task.metadata.as_ref().unwrap();
+        let chunk = data.downcast_ref::<ChunkTaskData>().unwrap().chunk;
+        match self.failures.get_mut(&chunk) {
+            Some(failures) if *failures > 0 => {
+                *failures -= 1;
+                (task, Err(anyhow::anyhow!("Scripted failure")))
+            }
+            _ => (task, result),
+        }
+    }
+
+    /// Discard and seed of every attempt at a chunk
+    fn attempts(&self, chunk: IVec2) -> Vec<(usize, u64)> {
+        self.queued
+            .iter()
+            .filter(|(queued, ..)| *queued == chunk)
+            .map(|&(_, discard, seed)| (discard, seed))
+            .collect()
+    }
+}
+
+/// Generates a world of 2 by 2 chunks, the chunk at `LAST` depends on all others
+fn generate(
+    backend: &mut Scripted,
+    recovery: ChunkRecovery,
+    painted: HashMap<IVec2, WaveFunction>,
+) -> (World, anyhow::Result<()>) {
+    let settings = common::settings(32);
+    let chunk_settings = ChunkSettings {
+        size: 16,
+        discard: 1,
+        recovery,
+        ..default()
+    };
+    generate_painted_world(
+        Arc::new(CarcassonneTileset::default()),
+        backend,
+        settings,
+        SEED,
+        GenerationMode::Deterministic,
+        chunk_settings,
+        WfcSettings::default(),
+        painted,
+    )
+}
+
+/// Discard and seed of the attempts expected for `LAST`, the discard grows with every retry
+fn expected_attempts(attempts: std::ops::Range<u32>, retries: u32) -> Vec<(usize, u64)> {
+    attempts
+        .map(|attempt| {
+            let discard = 1 + 2 * (attempt % (retries + 1)) as usize;
+            (discard, chunk_seed(SEED, LAST, attempt))
+        })
+        .collect()
+}
+
+#[test]
+fn failed_chunks_are_retried_with_a_larger_discard() {
+    let mut backend = Scripted::new(HashMap::from([(LAST, 2)]));
+    let recovery = ChunkRecovery {
+        retries: 2,
+        enlarge_discard: 2,
+        rollbacks: 0,
+    };
+    let (world, result) = generate(&mut backend, recovery, HashMap::new());
+    result.unwrap();
+
+    assert_eq!(backend.attempts(LAST), expected_attempts(0..3, 2));
+    // none of the other chunks had to be generated again
+    assert_eq!(backend.queued.len(), 6);
+    assert!(world
+        .world
+        .iter()
+        .flatten()
+        .all(|tiles| tiles.count_bits() == 1));
+}
+
+#[test]
+fn neighbors_are_rolled_back_once_retries_run_out() {
+    let mut backend = Scripted::new(HashMap::from([(LAST, 2)]));
+    let recovery = ChunkRecovery {
+        retries: 1,
+        enlarge_discard: 2,
+        rollbacks: 1,
+    };
+    let (world, result) = generate(&mut backend, recovery, HashMap::new());
+    result.unwrap();
+
+    assert_eq!(backend.attempts(LAST), expected_attempts(0..3, 1));
+    // the neighbors next to the chunk are generated again before its next attempt, the chunk
+    // diagonal to it is kept
+    let last = backend
+        .queued
+        .iter()
+        .rposition(|(chunk, ..)| *chunk == LAST);
+    let regenerated = &backend.queued[..last.unwrap()];
+    for (neighbor, times) in [
+        (IVec2::new(0, 1), 2),
+        (IVec2::new(1, 0), 2),
+        (IVec2::ZERO, 1),
+    ] {
+        let queued = regenerated.iter().filter(|(chunk, ..)| *chunk == neighbor);
+        assert_eq!(queued.count(), times, "chunk {:?}", neighbor);
+    }
+    assert!(world
+        .world
+        .iter()
+        .flatten()
+        .all(|tiles| tiles.count_bits() == 1));
+}
+
+#[test]
+fn chunks_that_can_never_succeed_fail_the_world() {
+    // the painted contradiction fails every attempt at the chunk
+    let mut backend = Scripted::new(HashMap::new());
+    let painted = HashMap::from([(IVec2::new(28, 28), WaveFunction::empty())]);
+    let recovery = ChunkRecovery {
+        retries: 1,
+        enlarge_discard: 2,
+        rollbacks: 2,
+    };
+    let (world, result) = generate(&mut backend, recovery, painted);
+    assert!(result.is_err());
+
+    // every rollback starts over with the original discard
+    assert_eq!(backend.attempts(LAST), expected_attempts(0..6, 1));
+    assert_eq!(world.outstanding, 0);
+}
@@ -0,0 +1,83 @@
+// helpers shared by the integration tests, not every test uses all of them
+#![allow(dead_code)]
+
+use core_wfc::{Graph, TileSet, WaveFunction};
+use grid_wfc::{
+    grid_graph::{self, Borders, GridGraphSettings},
+    world::World,
+};
+use std::path::PathBuf;
+
+/// Path of a file in the `assets` folder at the root of the repo
+pub fn asset(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../assets")
+        .join(path)
+}
+
+/// Square grid that is neither periodic nor restricted along its edges
+pub fn settings(size: usize) -> GridGraphSettings {
+    GridGraphSettings {
+        width: size,
+        height: size,
+        periodic: false,
+        diagonals: false,
+        borders: Borders::default(),
+    }
+}
+
+/// First rotation of the Carcassonne tile with grass on every side
+pub fn grass_tile(tileset: &dyn TileSet) -> usize {
+    let others = ["road", "city", "river"]
+        .iter()
+        .fold(WaveFunction::empty(), |others, tag| {
+            WaveFunction::join(&others, &tileset.tag_mask(tag))
+        });
+    WaveFunction::difference(&tileset.tag_mask("grass"), &others)
+        .tile_iter()
+        .next()
+        .unwrap()
+}
+
+/// Checks that every node is collapsed and allows the tile of each of its neighbors
+pub fn assert_valid(tileset: &dyn TileSet, graph: &Graph<WaveFunction>) {
+    let constraints = tileset.get_constraints();
+    for (index, tiles) in graph.tiles.iter().enumerate() {
+        let tile = tiles.collapse().unwrap();
+        for neighbor in graph.neighbors[index].iter() {
+            let other = graph.tiles[neighbor.index].collapse().unwrap();
+            assert!(
+                constraints[tile][neighbor.direction].contains(other),
+                "node {} doesn't fit its neighbor {}",
+                index,
+                neighbor.index
+            );
+        }
+    }
+}
+
+/// The cells of a world as a grid graph connected the way the world was generated, wrapping
+/// around when it is periodic
+pub fn world_graph(world: &World) -> Graph<WaveFunction> {
+    let size = world.size();
+    let settings = GridGraphSettings {
+        height: size.y as usize,
+        periodic: world.periodic,
+        diagonals: world.diagonals,
+        ..settings(size.x as usize)
+    };
+    let mut graph = grid_graph::create(&settings, WaveFunction::empty());
+    for (index, tiles) in graph.tiles.iter_mut().enumerate() {
+        *tiles = world.world[index % settings.width][index / settings.width].clone();
+    }
+    graph
+}
+
+/// Empty directory for the files written by a single test, unique to the test and the process
+/// running it so parallel test runs don't overwrite each other
+pub fn temp_dir(test: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("grid_wfc-{}-{}", test, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use core_wfc::{
+    graph_chunker::{GraphChunker, PartitionSettings},
+    wfc_backend::MultiThreaded,
+    wfc_task::WfcSettings,
+    Graph, Neighbor, TileSet, WaveFunction,
+};
+use grid_wfc::{carcassonne_tileset::CarcassonneTileset, grid_graph};
+use std::sync::Arc;
+
+#[test]
+fn shuffled_grid_is_generated_in_clusters() {
+    let tileset: Arc<dyn TileSet> = Arc::new(CarcassonneTileset::default());
+    let settings = common::settings(32);
+    let grid = grid_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+    let grid_partition = grid_graph::partition(&settings, 8);
+
+    // reorder the nodes so nothing can rely on the grid ordering
+    let count = grid.tiles.len();
+    let new_index = |node: usize| (node * 7 + 3) % count;
+    let mut graph = Graph {
+        tiles: grid.tiles.clone(),
+        neighbors: vec![Vec::new(); count],
+    };
+    let mut partition = vec![0; count];
+    for node in 0..count {
+        graph.neighbors[new_index(node)] = grid.neighbors[node]
+            .iter()
+            .map(|neighbor| Neighbor {
+                direction: neighbor.direction,
+                index: new_index(neighbor.index),
+            })
+            .collect();
+        partition[new_index(node)] = grid_partition[node];
+    }
+
+    let mut backend = MultiThreaded::new(4);
+    let mut chunker = GraphChunker::new(
+        graph,
+        partition,
+        tileset.clone(),
+        PartitionSettings::default(),
+        WfcSettings::default(),
+        5,
+    )
+    .unwrap();
+    chunker.start(&mut backend).unwrap();
+    chunker.run_to_completion(&mut backend).unwrap();
+
+    let result = chunker.graph.validate().unwrap();
+    let constraints = tileset.get_constraints();
+    for (node, neighbors) in result.neighbors.iter().enumerate() {
+        for neighbor in neighbors {
+            let allowed = &constraints[result.tiles[node]][neighbor.direction];
+            assert!(
+                allowed.contains(result.tiles[neighbor.index]),
+                "nodes {} and {} don't fit",
+                node,
+                neighbor.index
+            );
+        }
+    }
+}
+
+#[test]
+fn restricted_nodes_stay_restricted() {
+    let tileset = Arc::new(CarcassonneTileset::default());
+    let settings = common::settings(32);
+    let mut graph = grid_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+    // no cities along a row crossing every column of clusters
+    let city = tileset.tag_mask("city");
+    let allowed = WaveFunction::difference(&WaveFunction::filled(tileset.tile_count()), &city);
+    // Note: Assumes that the graph is a grid graph with a standard ordering
+    let restricted = 10 * 32..11 * 32;
+    for node in restricted.clone() {
+        graph.tiles[node] = allowed.clone();
+    }
+
+    let mut backend = MultiThreaded::new(4);
+    let mut chunker = GraphChunker::new(
+        graph,
+        grid_graph::partition(&settings, 8),
+        tileset,
+        PartitionSettings::default(),
+        WfcSettings::default(),
+        5,
+    )
+    .unwrap();
+    chunker.start(&mut backend).unwrap();
+    chunker.run_to_completion(&mut backend).unwrap();
+
+    let result = chunker.graph.validate().unwrap();
+    for node in restricted {
+        assert!(
+            !city.contains(result.tiles[node]),
+            "node {} has a city",
+            node
+        );
+    }
+}
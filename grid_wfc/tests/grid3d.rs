@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+use core_wfc::{
+    wfc_backend::MultiThreaded, wfc_task::WfcSettings, TileRender, TileSet, WaveFunction,
+};
+use grid_wfc::{
+    grid3d_graph::{self, Direction3d, GridGraph3dSettings, ROTATIONS},
+    voxel_tileset::VoxelTileset,
+    world::ChunkSettings,
+    world3d::World3d,
+};
+use std::sync::Arc;
+
+mod common;
+
+#[test]
+fn rotations_are_distinct_and_keep_opposites() {
+    let mut seen = Vec::new();
+    for rotation in 0..ROTATIONS {
+        let rotated: Vec<_> = (0..6)
+            .map(|direction| Direction3d::from(direction).rotate(rotation))
+            .collect();
+        for direction in 0..6 {
+            assert_eq!(
+                rotated[Direction3d::from(direction).other() as usize],
+                rotated[direction].other()
+            );
+        }
+        assert!(
+            !seen.contains(&rotated),
+            "rotation {} is a duplicate",
+            rotation
+        );
+        seen.push(rotated);
+    }
+    assert_eq!(seen[0], (0..6).map(Direction3d::from).collect::<Vec<_>>());
+}
+
+#[test]
+fn volume_is_generated_in_chunks() {
+    let tileset: Arc<dyn TileSet> = Arc::new(VoxelTileset::default());
+    let mut world = World3d::new(
+        IVec3::new(20, 12, 16),
+        tileset.clone(),
+        ChunkSettings {
+            size: 6,
+            overlap: 2,
+            ..default()
+        },
+        WfcSettings::default(),
+        9,
+    );
+    let mut backend = MultiThreaded::new(4);
+    world.generate(&mut backend).unwrap();
+
+    let size = world.size();
+    let settings = GridGraph3dSettings {
+        width: size.x as usize,
+        height: size.y as usize,
+        depth: size.z as usize,
+        ..default()
+    };
+    let mut graph = grid3d_graph::create(&settings, WaveFunction::empty());
+    for (x, column) in world.world.iter().enumerate() {
+        for (y, row) in column.iter().enumerate() {
+            for (z, tiles) in row.iter().enumerate() {
+                let pos = IVec3::new(x as i32, y as i32, z as i32);
+                graph.tiles[settings.index(pos)] = tiles.clone();
+            }
+        }
+    }
+    common::assert_valid(tileset.as_ref(), &graph);
+
+    let path = common::temp_dir("save_vox").join("world3d.vox");
+    world.save_vox(&path).unwrap();
+    let bytes = std::fs::read(path).unwrap();
+    assert_eq!(&bytes[..4], b"VOX ");
+    let chunk = |id: &[u8]| {
+        let start = bytes.windows(4).position(|window| window == id).unwrap() + 12;
+        let len = i32::from_le_bytes(bytes[start - 8..start - 4].try_into().unwrap());
+        &bytes[start..start + len as usize]
+    };
+    let values = |bytes: &[u8]| -> Vec<i32> {
+        bytes
+            .chunks(4)
+            .map(|value| i32::from_le_bytes(value.try_into().unwrap()))
+            .collect()
+    };
+
+    // MagicaVoxel is z up, so bevy's (x, y, z) is saved as (x, -z, y)
+    assert_eq!(values(chunk(b"SIZE")), [size.x, size.z, size.y]);
+    let assets = tileset.get_render_tile_assets();
+    let mut expected = Vec::new();
+    for (x, column) in world.world.iter().enumerate() {
+        for (y, row) in column.iter().enumerate() {
+            for (z, tiles) in row.iter().enumerate() {
+                let tile = tileset.get_render_tile(tiles.collapse().unwrap());
+                if matches!(assets[tile].0, TileRender::Color(color) if color.a() > 0.0) {
+                    expected.push([x as u8, (size.z as usize - 1 - z) as u8, y as u8]);
+                }
+            }
+        }
+    }
+    let xyzi = chunk(b"XYZI");
+    assert_eq!(values(&xyzi[..4]), [expected.len() as i32]);
+    let mut saved: Vec<[u8; 3]> = xyzi[4..]
+        .chunks(4)
+        .map(|voxel| [voxel[0], voxel[1], voxel[2]])
+        .collect();
+    expected.sort();
+    saved.sort();
+    assert!(!saved.is_empty());
+    assert_eq!(saved, expected);
+}
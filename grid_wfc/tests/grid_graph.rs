@@ -0,0 +1,94 @@
+mod common;
+
+use core_wfc::{
+    wfc_backend::SingleThreaded, wfc_task::WfcSettings, TileSet, WaveFunction, WfcTask,
+};
+use grid_wfc::{
+    grid_graph::{self, Direction, GridGraphSettings},
+    mxgmn_tileset::MxgmnTileset,
+};
+use std::sync::Arc;
+
+#[test]
+fn diagonal_directions_rotate_and_invert() {
+    for direction in (0..8).map(Direction::from) {
+        assert_eq!(direction.other().other(), direction);
+        assert_eq!(direction.rotate(2), direction.other());
+        assert_eq!(direction.rotate(4), direction);
+    }
+    assert_eq!(Direction::Up.rotate(1), Direction::Right);
+    assert_eq!(Direction::UpLeft.rotate(1), Direction::UpRight);
+    assert_eq!(Direction::DownRight.other(), Direction::UpLeft);
+}
+
+#[test]
+fn diagonals_connect_eight_neighbors() {
+    let settings = GridGraphSettings {
+        diagonals: true,
+        ..common::settings(3)
+    };
+    let graph = grid_graph::create(&settings, ());
+
+    // Note: Assumes that the graph is a grid graph with a standard ordering
+    let center = graph.neighbors[4].clone();
+    assert_eq!(center.len(), 8);
+    for neighbor in center {
+        let offset = Direction::from(neighbor.direction).to_ivec2();
+        assert_eq!(neighbor.index as i32, 4 + offset.y * 3 + offset.x);
+    }
+    assert_eq!(graph.neighbors[0].len(), 3);
+}
+
+#[test]
+fn diagonal_rules_are_enforced() {
+    // land and sea can be next to each other but never touch diagonally
+    let xml = r#"<set>
+  <tiles>
+    <tile name="land" symmetry="X"/>
+    <tile name="sea" symmetry="X"/>
+  </tiles>
+  <neighbors>
+    <neighbor left="land" right="land"/>
+    <neighbor left="land" right="sea"/>
+    <neighbor left="sea" right="sea"/>
+    <neighbor left="land" right="land" diagonal="true"/>
+    <neighbor left="sea" right="sea" diagonal="true"/>
+  </neighbors>
+</set>"#;
+    let path = common::temp_dir("diagonal_rules").join("Coast.xml");
+    std::fs::write(&path, xml).unwrap();
+    let tileset = MxgmnTileset::new(&path, None).unwrap();
+
+    let (land, sea) = (tileset.tag_mask("land"), tileset.tag_mask("sea"));
+    let constraints = tileset.get_constraints();
+    let land_tile = land.collapse().unwrap();
+    assert_eq!(
+        constraints[land_tile][Direction::Right as usize].count_bits(),
+        2
+    );
+    for direction in 4..8 {
+        assert_eq!(constraints[land_tile][direction], land);
+    }
+
+    let settings = GridGraphSettings {
+        diagonals: true,
+        ..common::settings(12)
+    };
+    // the only way to mix them is a checkerboard
+    let mut mixed = false;
+    for seed in 0..4 {
+        let graph = grid_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+        let mut task = WfcTask {
+            graph,
+            tileset: Arc::new(tileset.clone()),
+            seed,
+            metadata: None,
+            settings: WfcSettings::default(),
+            update_channel: None,
+        };
+        SingleThreaded::execute(&mut task).unwrap();
+        common::assert_valid(&tileset, &task.graph);
+        mixed |= task.graph.tiles.contains(&land) && task.graph.tiles.contains(&sea);
+    }
+    assert!(mixed);
+}
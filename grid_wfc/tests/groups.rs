@@ -0,0 +1,108 @@
+mod common;
+
+use core_wfc::{
+    wfc_backend::SingleThreaded,
+    wfc_task::{BacktrackingHeuristic, BacktrackingSettings, WfcSettings},
+    TileSet, WaveFunction, WfcTask,
+};
+use grid_wfc::{grid_graph, mxgmn_tileset::MxgmnTileset};
+use rand::{rngs::SmallRng, SeedableRng};
+use std::sync::Arc;
+
+#[test]
+fn group_rules_expand_to_tiles() {
+    let summer = MxgmnTileset::new(&common::asset("mxgmn/SummerGroups.xml"), None).unwrap();
+    let groups = summer.get_groups();
+    let wet = groups.iter().find(|group| group.name == "wet").unwrap();
+    assert_eq!(wet.parent, None);
+    // children add their tiles to the parent
+    assert_eq!(
+        wet.tiles,
+        WaveFunction::join(&summer.tag_mask("water"), &summer.tag_mask("shore"))
+    );
+    assert_eq!(summer.tag_mask("wet"), wet.tiles);
+
+    // the same tileset with the group rules written out per tile
+    let xml = std::fs::read_to_string(common::asset("mxgmn/SummerGroups.xml")).unwrap();
+    let mut explicit = String::new();
+    for line in xml.lines() {
+        match line.trim().strip_prefix("<neighbor left=\"water 0\"") {
+            Some(right) => {
+                for water in ["water_a", "water_b", "water_c"] {
+                    explicit += &format!("<neighbor left=\"{} 0\"{}\n", water, right);
+                }
+            }
+            None => explicit += &format!("{}\n", line),
+        }
+    }
+    let path = common::temp_dir("group_rules_expand_to_tiles").join("Explicit.xml");
+    std::fs::write(&path, explicit).unwrap();
+    let explicit = MxgmnTileset::new(&path, None).unwrap();
+    assert_eq!(summer.get_constraints(), explicit.get_constraints());
+}
+
+#[test]
+fn cells_collapse_to_groups_first() {
+    let tileset =
+        Arc::new(MxgmnTileset::new(&common::asset("mxgmn/SummerGroups.xml"), None).unwrap());
+    let settings = common::settings(16);
+    let graph = grid_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+    let mut task = WfcTask {
+        graph,
+        tileset: tileset.clone(),
+        seed: 2,
+        metadata: None,
+        settings: WfcSettings {
+            groups_first: true,
+            ..Default::default()
+        },
+        update_channel: None,
+    };
+
+    // top level first, then the children of the group the cell is in
+    let mut rng = SmallRng::seed_from_u64(0);
+    let (land, wet) = (tileset.tag_mask("land"), tileset.tag_mask("wet"));
+    let group = task.select_group(0, &mut rng).unwrap();
+    assert!(group == land || group == wet);
+    task.graph.tiles[0] = land;
+    let group = task.select_group(0, &mut rng).unwrap();
+    let grass = tileset.tag_mask("grass");
+    assert!([tileset.tag_mask("paths"), tileset.tag_mask("cliffs"), grass].contains(&group));
+    task.graph.tiles[0] = tileset.tag_mask("water");
+    assert_eq!(task.select_group(0, &mut rng), None);
+
+    task.graph.tiles[0] = WaveFunction::filled(tileset.tile_count());
+    SingleThreaded::execute(&mut task).unwrap();
+    common::assert_valid(tileset.as_ref(), &task.graph);
+}
+
+#[test]
+fn groups_first_backtracks() {
+    let tileset =
+        Arc::new(MxgmnTileset::new(&common::asset("mxgmn/SummerGroups.xml"), None).unwrap());
+    let settings = common::settings(32);
+    let graph = grid_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+    let mut task = WfcTask {
+        graph,
+        tileset: tileset.clone(),
+        seed: 3,
+        metadata: None,
+        settings: WfcSettings {
+            backtracking: BacktrackingSettings::Enabled {
+                restarts_left: 1000,
+                heuristic: BacktrackingHeuristic::Standard,
+            },
+            groups_first: true,
+            ..Default::default()
+        },
+        update_channel: None,
+    };
+    SingleThreaded::execute(&mut task).unwrap();
+
+    // the seed is picked to backtrack at least once
+    assert!(matches!(
+        task.settings.backtracking,
+        BacktrackingSettings::Enabled { restarts_left, .. } if restarts_left < 1000
+    ));
+    common::assert_valid(tileset.as_ref(), &task.graph);
+}
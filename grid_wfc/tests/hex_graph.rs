@@ -0,0 +1,69 @@
+use core_wfc::{
+    graph_chunker::{GraphChunker, PartitionSettings},
+    wfc_backend::MultiThreaded,
+    wfc_task::WfcSettings,
+    TileSet, WaveFunction,
+};
+use grid_wfc::{
+    hex_graph::{self, HexDirection, HexGraphSettings},
+    hex_tileset::HexTileset,
+};
+use std::sync::Arc;
+
+#[test]
+fn periodic_neighbors_are_mutual() {
+    let settings = HexGraphSettings {
+        width: 5,
+        height: 4,
+        periodic: true,
+    };
+    let graph = hex_graph::create(&settings, ());
+
+    for (node, neighbors) in graph.neighbors.iter().enumerate() {
+        assert_eq!(neighbors.len(), 6);
+        for neighbor in neighbors {
+            let other = HexDirection::from(neighbor.direction).other() as usize;
+            assert!(
+                graph.neighbors[neighbor.index]
+                    .iter()
+                    .any(|back| back.direction == other && back.index == node),
+                "node {} is not a neighbor of {}",
+                node,
+                neighbor.index
+            );
+        }
+    }
+}
+
+#[test]
+fn hex_map_is_generated_in_chunks() {
+    let tileset = Arc::new(HexTileset::default());
+    let settings = HexGraphSettings {
+        width: 32,
+        height: 32,
+        periodic: false,
+    };
+    let graph = hex_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+
+    let mut backend = MultiThreaded::new(4);
+    let mut chunker = GraphChunker::new(
+        graph,
+        hex_graph::partition(&settings, 8),
+        tileset.clone(),
+        PartitionSettings::default(),
+        WfcSettings::default(),
+        11,
+    )
+    .unwrap();
+    chunker.start(&mut backend).unwrap();
+    chunker.run_to_completion(&mut backend).unwrap();
+
+    let result = chunker.graph.validate().unwrap();
+    let constraints = tileset.get_constraints();
+    for (node, neighbors) in result.neighbors.iter().enumerate() {
+        for neighbor in neighbors {
+            let allowed = &constraints[result.tiles[node]][neighbor.direction];
+            assert!(allowed.contains(result.tiles[neighbor.index]));
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use bevy::{math::IRect, prelude::*, utils::HashMap};
+use core_wfc::{
+    wfc_backend::{Backend, SingleThreaded},
+    wfc_task::WfcSettings,
+    TileSet, WfcTask,
+};
+use grid_wfc::{
+    carcassonne_tileset::CarcassonneTileset,
+    chunk_store::{ChunkStore, FileChunkStore, StoredChunk},
+    infinite_world::InfiniteWorld,
+    world::{self, ChunkSettings, GenerationMode},
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+mod common;
+
+fn deterministic_world(seed: u64) -> InfiniteWorld {
+    let tileset: Arc<dyn TileSet> = Arc::new(CarcassonneTileset::default());
+    InfiniteWorld::new(
+        tileset,
+        ChunkSettings {
+            size: 8,
+            ..default()
+        },
+        WfcSettings::default(),
+        seed,
+        GenerationMode::Deterministic,
+    )
+}
+
+#[test]
+fn regions_are_independent_of_request_order() {
+    let mut backend = SingleThreaded::new();
+    let area = IRect::new(-8, -8, 24, 24);
+
+    let mut a = deterministic_world(7);
+    a.generate_region(&mut backend, area).unwrap();
+
+    // overlapping and disjoint regions first, including ones far outside of the area
+    let mut b = deterministic_world(7);
+    let regions = [
+        IRect::new(20, 20, 30, 30),
+        IRect::new(-40, 0, -10, 8),
+        IRect::new(0, -8, 24, 0),
+        area,
+    ];
+    for region in regions {
+        b.generate_region(&mut backend, region).unwrap();
+    }
+
+    for y in area.min.y..area.max.y {
+        for x in area.min.x..area.max.x {
+            let pos = IVec2::new(x, y);
+            assert_eq!(a.get_cell(pos), b.get_cell(pos), "cell {:?} differs", pos);
+        }
+    }
+}
+
+#[test]
+fn unloaded_chunks_are_reloaded_from_the_store() {
+    let mut backend = SingleThreaded::new();
+    let area = IRect::new(0, 0, 16, 16);
+    let directory = common::temp_dir("chunk_store");
+
+    let mut expected = deterministic_world(3);
+    expected.generate_region(&mut backend, area).unwrap();
+
+    let mut world = deterministic_world(3);
+    world.store = Some(Box::new(FileChunkStore::new(&directory).unwrap()));
+    world.generate_region(&mut backend, area).unwrap();
+    world.unload_outside(IVec2::new(100, 100), 0).unwrap();
+    assert!(world.chunks.is_empty() && world.generated_chunks.is_empty());
+
+    // generating the region again only loads the stored chunks
+    world.request_chunks(&mut backend, [IVec2::ZERO]).unwrap();
+    assert_eq!(world.outstanding, 0);
+    for y in area.min.y..area.max.y {
+        for x in area.min.x..area.max.x {
+            let pos = IVec2::new(x, y);
+            assert_eq!(world.get_cell(pos), expected.get_cell(pos));
+        }
+    }
+}
+
+/// Store without any chunks that counts how often each chunk is looked up
+#[derive(Clone, Default)]
+struct CountLoads {
+    loads: Arc<Mutex<HashMap<IVec2, usize>>>,
+}
+
+impl ChunkStore for CountLoads {
+    fn save(&mut self, _chunk: IVec2, _data: &StoredChunk) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn load(&mut self, chunk: IVec2) -> anyhow::Result<Option<StoredChunk>> {
+        *self.loads.lock().unwrap().entry(chunk).or_insert(0) += 1;
+        Ok(None)
+    }
+}
+
+#[test]
+fn missing_chunks_are_looked_up_once() {
+    let store = CountLoads::default();
+    let mut world = deterministic_world(5);
+    world.store = Some(Box::new(store.clone()));
+    world
+        .generate_region(&mut SingleThreaded::new(), IRect::new(0, 0, 32, 32))
+        .unwrap();
+
+    let loads = store.loads.lock().unwrap();
+    assert!(!loads.is_empty());
+    assert!(loads.values().all(|&count| count == 1));
+}
+
+/// Backend that remembers the seed of every task queued on it
+struct RecordSeeds {
+    backend: SingleThreaded,
+    seeds: Vec<u64>,
+}
+
+impl Backend for RecordSeeds {
+    fn queue_task(&mut self, task: WfcTask) -> anyhow::Result<()> {
+        self.seeds.push(task.seed);
+        self.backend.queue_task(task)
+    }
+
+    fn get_output(&mut self) -> Option<(WfcTask, anyhow::Result<()>)> {
+        self.backend.get_output()
+    }
+
+    fn wait_for_output(&mut self) -> (WfcTask, anyhow::Result<()>) {
+        self.backend.wait_for_output()
+    }
+
+    fn clear(&mut self) {
+        self.backend.clear()
+    }
+}
+
+#[test]
+fn failed_chunks_are_retried_with_the_next_seed() {
+    let mut backend = RecordSeeds {
+        backend: SingleThreaded::new(),
+        seeds: Vec::new(),
+    };
+    let mut world = deterministic_world(7);
+    // every chunk times out as soon as it starts
+    world.settings.timeout = Some(Duration::ZERO);
+
+    for _ in 0..3 {
+        world.request_chunks(&mut backend, [IVec2::ZERO]).unwrap();
+        assert!(world.wait_for_requests(&mut backend).is_err());
+    }
+    let expected: Vec<_> = (0..3)
+        .map(|attempt| world::chunk_seed(7, IVec2::ZERO, attempt))
+        .collect();
+    assert_eq!(backend.seeds, expected);
+    assert_eq!(world.attempts[&IVec2::ZERO], 3);
+}
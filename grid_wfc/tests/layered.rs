@@ -0,0 +1,120 @@
+mod common;
+
+use core_wfc::{
+    wfc_backend::SingleThreaded,
+    wfc_task::{TileCount, WfcSettings},
+    TileSet, WaveFunction,
+};
+use grid_wfc::{
+    carcassonne_tileset::CarcassonneTileset,
+    layered::{generate_layers, mapping_from_tags, Layer},
+    mxgmn_tileset::MxgmnTileset,
+};
+use std::sync::Arc;
+
+#[test]
+fn coarse_tiles_restrict_their_blocks() {
+    let tileset = Arc::new(MxgmnTileset::new(&common::asset("mxgmn/Summer.xml"), None).unwrap());
+    let water = tileset.tag_mask("water");
+    let shore = tileset.tag_mask("shore");
+    let wet = WaveFunction::join(&water, &shore);
+    let dry = WaveFunction::difference(&WaveFunction::filled(tileset.tile_count()), &water);
+    let mapping = (0..tileset.tile_count())
+        .map(|tile| match water.contains(tile) {
+            true => wet.clone(),
+            false => dry.clone(),
+        })
+        .collect();
+    let layers = [Layer {
+        tileset: tileset.clone(),
+        scale: 4,
+        mapping,
+    }];
+
+    let settings = common::settings(8);
+    let (graphs, result) = generate_layers(
+        tileset.clone(),
+        &mut SingleThreaded::new(),
+        settings,
+        5,
+        WfcSettings::default(),
+        &layers,
+    );
+    result.unwrap();
+    assert_eq!(graphs.len(), 2);
+
+    let (coarse, fine) = (&graphs[0], &graphs[1]);
+    assert_eq!(fine.tiles.len(), 32 * 32);
+    common::assert_valid(tileset.as_ref(), fine);
+    for (index, tiles) in fine.tiles.iter().enumerate() {
+        let tile = tiles.collapse().unwrap();
+        let (x, y) = (index % 32, index / 32);
+        let coarse_tile = coarse.tiles[y / 4 * 8 + x / 4].collapse().unwrap();
+        match water.contains(coarse_tile) {
+            true => assert!(wet.contains(tile)),
+            false => assert!(!water.contains(tile)),
+        }
+    }
+}
+
+#[test]
+fn counts_apply_to_the_coarsest_layer() {
+    let tileset: Arc<dyn TileSet> = Arc::new(CarcassonneTileset::default());
+    let layers = [Layer {
+        tileset: tileset.clone(),
+        scale: 2,
+        mapping: mapping_from_tags(tileset.as_ref(), tileset.as_ref()),
+    }];
+
+    let settings = common::settings(8);
+    let (graphs, result) = generate_layers(
+        tileset.clone(),
+        &mut SingleThreaded::new(),
+        settings,
+        3,
+        WfcSettings {
+            counts: vec![TileCount {
+                tiles: vec![5],
+                min: 1,
+                max: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+        &layers,
+    );
+    result.unwrap();
+
+    let coarse = &graphs[0];
+    let count = coarse
+        .tiles
+        .iter()
+        .filter(|tiles| tiles.collapse() == Some(5))
+        .count();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn mismatched_mapping_is_rejected() {
+    let tileset: Arc<dyn TileSet> = Arc::new(CarcassonneTileset::default());
+    let mut mapping = mapping_from_tags(tileset.as_ref(), tileset.as_ref());
+    assert_eq!(mapping.len(), tileset.tile_count());
+    mapping.pop();
+
+    let layers = [Layer {
+        tileset: tileset.clone(),
+        scale: 2,
+        mapping,
+    }];
+    let settings = common::settings(4);
+    let (graphs, result) = generate_layers(
+        tileset,
+        &mut SingleThreaded::new(),
+        settings,
+        0,
+        WfcSettings::default(),
+        &layers,
+    );
+    assert!(result.is_err());
+    assert_eq!(graphs.len(), 1);
+}
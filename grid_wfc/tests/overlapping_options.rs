@@ -0,0 +1,98 @@
+mod common;
+
+use core_wfc::{
+    wfc_backend::SingleThreaded, wfc_task::WfcSettings, TileSet, WaveFunction, WfcTask,
+};
+use grid_wfc::{
+    grid_graph,
+    overlapping_tileset::{OverlappingSettings, OverlappingTileset},
+};
+use std::sync::Arc;
+
+fn load_sample(name: &str, settings: &OverlappingSettings) -> OverlappingTileset {
+    OverlappingTileset::from_image(&common::asset(&format!("samples/{}.png", name)), settings)
+}
+
+/// Solves a non periodic grid of 24 by 24 cells with the ground applied
+fn generate(tileset: OverlappingTileset, seed: u64) -> Vec<WaveFunction> {
+    let settings = common::settings(24);
+    let mut graph = grid_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+    tileset.constrain_ground(&mut graph, settings.width);
+    let mut task = WfcTask {
+        graph,
+        tileset: Arc::new(tileset),
+        seed,
+        metadata: None,
+        settings: WfcSettings::default(),
+        update_channel: None,
+    };
+
+    SingleThreaded::execute(&mut task).unwrap();
+    task.graph.tiles
+}
+
+#[test]
+fn ground_fills_the_bottom_row() {
+    for (name, seed) in [("Skyline", 0), ("Platformer", 0)] {
+        let settings = OverlappingSettings {
+            overlap: 1,
+            symmetry: 2,
+            ground: true,
+            ..Default::default()
+        };
+        let tileset = load_sample(name, &settings);
+        let ground = tileset.get_ground().unwrap();
+
+        // Note: Assumes that the graph is a grid graph with a standard ordering
+        let tiles = generate(tileset, seed);
+        for (index, tiles) in tiles.iter().enumerate() {
+            let tile = tiles.collapse().unwrap();
+            assert_eq!(tile == ground, index < 24, "{} cell {}", name, index);
+        }
+    }
+}
+
+#[test]
+fn non_periodic_input_keeps_patterns_inside_the_sample() {
+    // indexed as sample[x][y], every pixel is unique so every window is a new pattern
+    let sample = vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 7, 8]];
+    let settings = |periodic_input| OverlappingSettings {
+        overlap: 1,
+        symmetry: 1,
+        periodic_input,
+        ..Default::default()
+    };
+    let periodic = OverlappingTileset::new(sample.clone(), &settings(true));
+    let non_periodic = OverlappingTileset::new(sample, &settings(false));
+    assert_eq!(periodic.tile_count(), 9);
+    // only the window around the center fits
+    assert_eq!(non_periodic.tile_count(), 1);
+
+    // a sample that is not meant to wrap still generates
+    let settings = OverlappingSettings {
+        overlap: 1,
+        periodic_input: false,
+        ..Default::default()
+    };
+    let tiles = generate(load_sample("Rooms", &settings), 1);
+    assert!(tiles.iter().all(|tiles| tiles.collapse().is_some()));
+}
+
+#[test]
+fn symmetry_adds_rotations_and_reflections() {
+    // indexed as sample[x][y], no window repeats in any rotation or reflection
+    let sample: Vec<Vec<usize>> = (0..4)
+        .map(|x| (0..4).map(|y| x * 4 + y).collect())
+        .collect();
+    let patterns = |symmetry| {
+        let settings = OverlappingSettings {
+            overlap: 1,
+            symmetry,
+            ..Default::default()
+        };
+        OverlappingTileset::new(sample.clone(), &settings).tile_count()
+    };
+    assert_eq!(patterns(1), 16);
+    assert_eq!(patterns(2), 32);
+    assert_eq!(patterns(8), 128);
+}
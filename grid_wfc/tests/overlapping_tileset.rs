@@ -0,0 +1,260 @@
+use bevy::prelude::*;
+use core_wfc::{
+    wfc_backend::SingleThreaded, wfc_task::WfcSettings, TileRender, TileSet, WaveFunction, WfcTask,
+};
+use grid_wfc::{
+    grid_graph::{self, GridGraphSettings},
+    overlapping_tileset::{OverlappingSettings, OverlappingTileset},
+    single_shot::generate_world,
+    world::{ChunkSettings, GenerationMode},
+};
+use std::sync::Arc;
+
+mod common;
+
+fn generate(tileset: OverlappingTileset, seed: u64) -> Vec<WaveFunction> {
+    let settings = GridGraphSettings {
+        periodic: true,
+        ..common::settings(24)
+    };
+    let mut graph = tileset.create_graph(&settings, WaveFunction::filled(tileset.tile_count()));
+    tileset.constrain_ground(&mut graph, settings.width);
+    let mut task = WfcTask {
+        graph,
+        tileset: Arc::new(tileset),
+        seed,
+        metadata: None,
+        settings: WfcSettings::default(),
+        update_channel: None,
+    };
+
+    SingleThreaded::execute(&mut task).unwrap();
+    task.graph.tiles
+}
+
+#[test]
+fn patterns_are_indexed_in_first_occurrence_order() {
+    // indexed as sample[x][y]
+    let sample = vec![vec![0, 0, 1], vec![0, 1, 1], vec![2, 2, 1]];
+    let settings = OverlappingSettings {
+        overlap: 0,
+        symmetry: 1,
+        ..default()
+    };
+    let tileset = OverlappingTileset::new(sample, &settings);
+
+    let centers = (0..tileset.tile_count())
+        .map(|pattern| tileset.get_center_tile(pattern).0)
+        .collect::<Vec<_>>();
+    assert_eq!(centers, vec![0, 2, 1]);
+    assert_eq!(*tileset.get_weights(), vec![3.0, 2.0, 4.0]);
+}
+
+#[test]
+fn colors_are_indexed_in_first_occurrence_order() {
+    let path = common::asset("samples/Flowers.png");
+    let tileset = OverlappingTileset::from_image(&path, &OverlappingSettings::default());
+
+    let image = image::open(&path).unwrap().to_rgba8();
+    let pixel = image.get_pixel(0, 0);
+    let expected = Color::rgb(
+        pixel[0] as f32 / 255.0,
+        pixel[1] as f32 / 255.0,
+        pixel[2] as f32 / 255.0,
+    );
+    match &tileset.get_render_tile_assets()[0].0 {
+        TileRender::Color(color) => assert_eq!(*color, expected),
+        TileRender::Sprite(_) => panic!("expected a color tile"),
+    }
+}
+
+#[test]
+fn same_sample_and_seed_give_identical_output() {
+    let settings = OverlappingSettings {
+        symmetry: 2,
+        ground: true,
+        ..default()
+    };
+    let a = OverlappingTileset::from_image(&common::asset("samples/Flowers.png"), &settings);
+    let b = OverlappingTileset::from_image(&common::asset("samples/Flowers.png"), &settings);
+
+    assert_eq!(a.get_weights(), b.get_weights());
+    assert_eq!(a.get_constraints(), b.get_constraints());
+    assert_eq!(a.get_ground(), b.get_ground());
+    assert_eq!(generate(a, 42), generate(b, 42));
+}
+
+#[test]
+fn render_image_averages_remaining_patterns() {
+    let settings = OverlappingSettings {
+        overlap: 0,
+        symmetry: 1,
+        ..default()
+    };
+    let tileset = OverlappingTileset::new(vec![vec![0], vec![1]], &settings);
+
+    let settings = GridGraphSettings {
+        height: 1,
+        ..common::settings(3)
+    };
+    let mut graph = grid_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+    graph.tiles[0] = WaveFunction::single(1);
+    graph.tiles[2] = WaveFunction::empty();
+
+    let image = tileset.render_image(&graph, &settings);
+    assert_eq!(image.get_pixel(0, 0).0, [128, 128, 128, 255]);
+    assert_eq!(image.get_pixel(1, 0).0, [64, 64, 64, 255]);
+    assert_eq!(image.get_pixel(2, 0).0, [0, 0, 0, 0]);
+
+    let path = common::temp_dir("render_image").join("render.png");
+    tileset.save_image(&graph, &settings, &path).unwrap();
+    assert_eq!(image::open(&path).unwrap().to_rgba8(), image);
+
+    // the patterns of the cells next to a pixel cover it as well, pattern 0 is a column of tile
+    // 0 between columns of tile 1 and pattern 1 the other way around
+    let tileset = OverlappingTileset::new(
+        vec![vec![0], vec![1]],
+        &OverlappingSettings {
+            overlap: 1,
+            symmetry: 1,
+            ..default()
+        },
+    );
+    let mut graph = grid_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+    graph.tiles[0] = WaveFunction::single(0);
+    graph.tiles[1] = WaveFunction::single(1);
+
+    let image = tileset.render_image(&graph, &settings);
+    assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+    // tile 1 of its own pattern and the left one and either tile of the right cell's patterns
+    assert_eq!(image.get_pixel(1, 0).0, [96, 96, 96, 255]);
+}
+
+#[test]
+fn diagonal_neighbors_are_constrained() {
+    let tileset = OverlappingTileset::from_image(
+        &common::asset("samples/Flowers.png"),
+        &OverlappingSettings {
+            symmetry: 2,
+            ..default()
+        },
+    );
+    let constraints = tileset.get_constraints();
+    assert_eq!(constraints[0].len(), 8);
+
+    let settings = GridGraphSettings {
+        periodic: true,
+        diagonals: true,
+        ..common::settings(16)
+    };
+    let graph = tileset.create_graph(&settings, WaveFunction::filled(tileset.tile_count()));
+    let mut task = WfcTask {
+        graph,
+        tileset: Arc::new(tileset),
+        seed: 3,
+        metadata: None,
+        settings: WfcSettings::default(),
+        update_channel: None,
+    };
+    SingleThreaded::execute(&mut task).unwrap();
+
+    let result = task.graph.validate().unwrap();
+    for (node, neighbors) in result.neighbors.iter().enumerate() {
+        assert_eq!(neighbors.len(), 8);
+        for neighbor in neighbors {
+            let allowed = &constraints[result.tiles[node]][neighbor.direction];
+            assert!(allowed.contains(result.tiles[neighbor.index]));
+        }
+    }
+}
+
+#[test]
+fn chunked_worlds_keep_the_ground() {
+    let tileset = OverlappingTileset::from_image(
+        &common::asset("samples/Flowers.png"),
+        &OverlappingSettings {
+            symmetry: 2,
+            ground: true,
+            ..default()
+        },
+    );
+    let ground = tileset.get_ground().unwrap();
+    let settings = common::settings(32);
+    let chunk_settings = ChunkSettings {
+        size: 16,
+        overlap: 3,
+        // stems reach far above the ground
+        discard: 8,
+        ..default()
+    };
+    let (world, result) = generate_world(
+        Arc::new(tileset),
+        &mut SingleThreaded::new(),
+        settings,
+        1,
+        GenerationMode::Deterministic,
+        chunk_settings,
+        WfcSettings::default(),
+    );
+    result.unwrap();
+
+    for (x, column) in world.world.iter().enumerate() {
+        for (y, tiles) in column.iter().enumerate() {
+            assert_eq!(tiles.collapse() == Some(ground), y == 0, "cell {} {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn full_neighborhood_matches_every_offset() {
+    let overlap = 2;
+    let tileset = OverlappingTileset::from_image(
+        &common::asset("samples/SimpleKnot.png"),
+        &OverlappingSettings {
+            overlap,
+            full_neighborhood: true,
+            ..default()
+        },
+    );
+    let settings = GridGraphSettings {
+        periodic: true,
+        ..common::settings(16)
+    };
+    let graph = tileset.create_graph(&settings, WaveFunction::filled(tileset.tile_count()));
+    let mut task = WfcTask {
+        graph,
+        tileset: Arc::new(tileset.clone()),
+        seed: 0,
+        metadata: None,
+        settings: WfcSettings::default(),
+        update_channel: None,
+    };
+    SingleThreaded::execute(&mut task).unwrap();
+    let result = task.graph.validate().unwrap();
+    // every offset within the 5 by 5 window is a neighbor, not just the 8 around the cell
+    assert_eq!(tileset.get_constraints()[0].len(), 24);
+    assert!(result
+        .neighbors
+        .iter()
+        .all(|neighbors| neighbors.len() == 24));
+
+    // the pattern of every cell agrees with the center tile of each cell in its window
+    let (overlap, width) = (overlap as i32, overlap as i32 * 2 + 1);
+    for (node, &pattern) in result.tiles.iter().enumerate() {
+        let pos = IVec2::new(node as i32 % 16, node as i32 / 16);
+        let pattern = tileset.get_pattern(pattern);
+        for y in -overlap..=overlap {
+            for x in -overlap..=overlap {
+                let other = (pos + IVec2::new(x, y)).rem_euclid(IVec2::splat(16));
+                let other = result.tiles[(other.y * 16 + other.x) as usize];
+                assert_eq!(
+                    tileset.get_center_tile(other).0,
+                    pattern[((y + overlap) * width + x + overlap) as usize],
+                    "cell {} and its neighbor at {:?} overlap differently",
+                    pos,
+                    (x, y)
+                );
+            }
+        }
+    }
+}
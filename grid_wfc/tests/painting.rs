@@ -0,0 +1,80 @@
+mod common;
+
+use bevy::{prelude::*, utils::HashMap};
+use core_wfc::{wfc_backend::MultiThreaded, wfc_task::WfcSettings, TileSet, WaveFunction};
+use grid_wfc::{
+    carcassonne_tileset::CarcassonneTileset,
+    chunked_generator::ChunkedGenerator,
+    grid_graph::Direction,
+    single_shot,
+    world::{ChunkSettings, GenerationMode, World},
+};
+use std::sync::Arc;
+
+#[test]
+fn painted_cells_keep_their_tiles() {
+    let tileset: Arc<dyn TileSet> = Arc::new(CarcassonneTileset::default());
+    let painted = HashMap::from_iter([
+        (IVec2::new(3, 4), WaveFunction::single(0)),
+        // on the border between chunks
+        (IVec2::new(16, 16), WaveFunction::single(5)),
+        (IVec2::new(20, 9), WaveFunction::single(12)),
+    ]);
+
+    let (world, result) = single_shot::generate_painted_world(
+        tileset,
+        &mut MultiThreaded::new(4),
+        common::settings(32),
+        7,
+        GenerationMode::Deterministic,
+        ChunkSettings::default(),
+        WfcSettings::default(),
+        painted.clone(),
+    );
+    result.unwrap();
+
+    for (pos, tiles) in painted {
+        assert_eq!(world.world[pos.x as usize][pos.y as usize], tiles);
+    }
+}
+
+#[test]
+fn conflicting_paint_reports_the_cell() {
+    let tileset: Arc<dyn TileSet> = Arc::new(CarcassonneTileset::default());
+    let constraints = tileset.get_constraints();
+    let (left, right) = (0..tileset.tile_count())
+        .flat_map(|a| (0..tileset.tile_count()).map(move |b| (a, b)))
+        .find(|&(a, b)| !constraints[a][Direction::Right as usize].contains(b))
+        .unwrap();
+
+    let settings = common::settings(32);
+    let mut world = World::new(
+        tileset,
+        &settings,
+        ChunkSettings::default(),
+        WfcSettings::default(),
+        0,
+    );
+    world.paint(IVec2::new(5, 6), WaveFunction::single(left));
+    world.paint(IVec2::new(6, 6), WaveFunction::single(right));
+
+    let mut backend = MultiThreaded::new(4);
+    let mut generator =
+        ChunkedGenerator::start(&mut world, &mut backend, GenerationMode::Deterministic).unwrap();
+    assert!(generator
+        .run_to_completion(&mut world, &mut backend)
+        .is_err());
+
+    let error = generator
+        .results
+        .iter()
+        .find_map(|result| result.result.as_ref().err())
+        .unwrap()
+        .to_string();
+    assert!(
+        error == "Invalid initial state at cell [5, 6]"
+            || error == "Invalid initial state at cell [6, 6]",
+        "{}",
+        error
+    );
+}
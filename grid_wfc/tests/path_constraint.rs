@@ -0,0 +1,125 @@
+mod common;
+
+use core_wfc::{
+    path_constraint::PathConstraint,
+    wfc_backend::{MultiThreaded, SingleThreaded},
+    wfc_task::{GlobalConstraint, WfcSettings},
+    TileSet, WaveFunction, WfcTask,
+};
+use grid_wfc::{
+    carcassonne_tileset::CarcassonneTileset,
+    grid_graph::{self, GridGraphSettings},
+    overlapping_tileset::{OverlappingSettings, OverlappingTileset},
+    single_shot,
+    world::{ChunkSettings, GenerationMode},
+};
+use std::sync::Arc;
+
+fn line(tiles: Vec<WaveFunction>) -> core_wfc::Graph<WaveFunction> {
+    let settings = GridGraphSettings {
+        height: 1,
+        ..common::settings(tiles.len())
+    };
+    let mut graph = grid_graph::create(&settings, WaveFunction::empty());
+    graph.tiles = tiles;
+    graph
+}
+
+/// Number of 4-connected components formed by the walkable cells
+fn components(graph: &core_wfc::Graph<usize>, walkable: &[usize]) -> usize {
+    let mut seen = vec![false; graph.tiles.len()];
+    let mut components = 0;
+    for start in 0..graph.tiles.len() {
+        if seen[start] || !walkable.contains(&graph.tiles[start]) {
+            continue;
+        }
+        components += 1;
+        seen[start] = true;
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            for neighbor in graph.neighbors[node].iter() {
+                if neighbor.direction < 4
+                    && !seen[neighbor.index]
+                    && walkable.contains(&graph.tiles[neighbor.index])
+                {
+                    seen[neighbor.index] = true;
+                    stack.push(neighbor.index);
+                }
+            }
+        }
+    }
+    components
+}
+
+#[test]
+fn cells_between_walkable_cells_are_forced() {
+    let walkable = || WaveFunction::single(0);
+    let path = PathConstraint {
+        walkable: vec![0],
+        ..Default::default()
+    };
+
+    let mut graph = line(vec![walkable(), WaveFunction::filled(2), walkable()]);
+    assert_eq!(path.apply(&mut graph).unwrap(), vec![1]);
+    assert_eq!(graph.tiles[1], walkable());
+
+    let mut graph = line(vec![walkable(), WaveFunction::single(1), walkable()]);
+    assert!(path.apply(&mut graph).is_err());
+}
+
+#[test]
+fn walkable_rooms_are_connected() {
+    let tileset = OverlappingTileset::from_image(
+        &common::asset("samples/Rooms.png"),
+        &OverlappingSettings {
+            overlap: 1,
+            ..Default::default()
+        },
+    );
+    // white floors
+    let walkable = (0..tileset.tile_count())
+        .filter(|tile| tileset.get_render_tile(*tile) == 1)
+        .collect::<Vec<_>>();
+
+    let settings = common::settings(32);
+    let graph = tileset.create_graph(&settings, WaveFunction::filled(tileset.tile_count()));
+    let mut task = WfcTask {
+        graph,
+        tileset: Arc::new(tileset),
+        seed: 1,
+        metadata: None,
+        settings: WfcSettings {
+            path: Some(PathConstraint {
+                walkable: walkable.clone(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        update_channel: None,
+    };
+    SingleThreaded::execute(&mut task).unwrap();
+
+    let result = task.graph.validate().unwrap();
+    assert_eq!(components(&result, &walkable), 1);
+}
+
+#[test]
+fn chunked_world_rejects_path() {
+    let (world, result) = single_shot::generate_world(
+        Arc::new(CarcassonneTileset::default()),
+        &mut MultiThreaded::new(4),
+        common::settings(32),
+        0,
+        GenerationMode::Deterministic,
+        ChunkSettings::default(),
+        WfcSettings {
+            path: Some(PathConstraint {
+                walkable: vec![0],
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+    assert!(result.is_err());
+    assert!(world.generated_chunks.is_empty());
+}
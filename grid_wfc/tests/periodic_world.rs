@@ -0,0 +1,57 @@
+mod common;
+
+use core_wfc::{wfc_backend::MultiThreaded, wfc_task::WfcSettings};
+use grid_wfc::{
+    carcassonne_tileset::CarcassonneTileset,
+    grid_graph::GridGraphSettings,
+    single_shot,
+    world::{ChunkSettings, GenerationMode},
+};
+use std::sync::Arc;
+
+fn settings(size: usize) -> GridGraphSettings {
+    GridGraphSettings {
+        periodic: true,
+        ..common::settings(size)
+    }
+}
+
+#[test]
+fn chunked_worlds_wrap_around() {
+    // a single row of corners and edges and a larger world
+    for (size, seed) in [(32, 3), (64, 7)] {
+        let (world, result) = single_shot::generate_world(
+            Arc::new(CarcassonneTileset::default()),
+            &mut MultiThreaded::new(4),
+            settings(size),
+            seed,
+            GenerationMode::Deterministic,
+            ChunkSettings::default(),
+            WfcSettings::default(),
+        );
+        result.unwrap();
+        // the graph of a periodic world includes the neighbors across the edges
+        common::assert_valid(world.tileset.as_ref(), &common::world_graph(&world));
+    }
+}
+
+#[test]
+fn chunks_have_to_fit_the_periodic_world() {
+    let generate = |size, generation_mode| {
+        single_shot::generate_world(
+            Arc::new(CarcassonneTileset::default()),
+            &mut MultiThreaded::new(4),
+            settings(size),
+            0,
+            generation_mode,
+            ChunkSettings::default(),
+            WfcSettings::default(),
+        )
+        .1
+    };
+
+    assert!(generate(40, GenerationMode::Deterministic).is_err());
+    // three chunks can't alternate between corners and edges
+    assert!(generate(48, GenerationMode::Deterministic).is_err());
+    assert!(generate(64, GenerationMode::NonDeterministic).is_err());
+}
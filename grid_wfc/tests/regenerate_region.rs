@@ -0,0 +1,48 @@
+mod common;
+
+use bevy::prelude::*;
+use core_wfc::{wfc_backend::MultiThreaded, wfc_task::WfcSettings, TileSet};
+use grid_wfc::{
+    carcassonne_tileset::CarcassonneTileset,
+    single_shot,
+    world::{ChunkSettings, GenerationMode},
+};
+use std::sync::Arc;
+
+#[test]
+fn regenerated_region_fits_its_surroundings() {
+    let tileset: Arc<dyn TileSet> = Arc::new(CarcassonneTileset::default());
+    let (mut world, result) = single_shot::generate_world(
+        tileset,
+        &mut MultiThreaded::new(4),
+        common::settings(32),
+        3,
+        GenerationMode::Deterministic,
+        ChunkSettings::default(),
+        WfcSettings::default(),
+    );
+    result.unwrap();
+    let before = world.world.clone();
+
+    let (bottom_left, top_right) = (IVec2::new(10, 12), IVec2::new(20, 18));
+    world
+        .regenerate_region(bottom_left, top_right, 2, 9)
+        .unwrap();
+    common::assert_valid(world.tileset.as_ref(), &common::world_graph(&world));
+
+    let mut changed = false;
+    for x in 0..32 {
+        for y in 0..32 {
+            let pos = IVec2::new(x, y);
+            let in_halo = pos.cmpge(bottom_left - 2).all() && pos.cmplt(top_right + 2).all();
+            let same = world.world[x as usize][y as usize] == before[x as usize][y as usize];
+            assert!(in_halo || same, "cell {} outside the region changed", pos);
+            changed |= !same;
+        }
+    }
+    assert!(changed);
+
+    assert!(world
+        .regenerate_region(top_right, bottom_left, 0, 9)
+        .is_err());
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use core_wfc::{
+    wfc_backend::SingleThreaded,
+    wfc_task::{TileCount, WfcSettings},
+    TileSet, WaveFunction, WfcTask,
+};
+use grid_wfc::{
+    carcassonne_tileset::CarcassonneTileset, grid_graph, mxgmn_tileset::MxgmnTileset,
+    overlapping_tileset::OverlappingTileset, world::tiles_rendered_as,
+};
+use std::sync::Arc;
+
+#[test]
+fn tilesets_have_tags() {
+    let summer = MxgmnTileset::new(&common::asset("mxgmn/Summer.xml"), None).unwrap();
+    // from the tags attribute
+    assert_eq!(summer.tag_mask("water").count_bits(), 3);
+    // from the name, every rotation of the tile
+    assert_eq!(summer.tag_mask("roadturn").count_bits(), 4);
+    assert_eq!(summer.tag_mask("lava"), WaveFunction::empty());
+
+    let rooms =
+        OverlappingTileset::from_image(&common::asset("samples/Rooms.png"), &Default::default());
+    assert_eq!(rooms.get_tags().len(), 2);
+    assert_eq!(rooms.tag_mask("#ffffff"), tiles_rendered_as(&rooms, 1));
+
+    let carcassonne = CarcassonneTileset::default();
+    let tags = carcassonne.get_tags();
+    let all = tags.values().fold(WaveFunction::empty(), |all, mask| {
+        WaveFunction::join(&all, mask)
+    });
+    assert_eq!(all, WaveFunction::filled(carcassonne.tile_count()));
+}
+
+#[test]
+fn tags_limit_counts() {
+    let tileset = MxgmnTileset::new(&common::asset("mxgmn/Summer.xml"), None).unwrap();
+    let water = tileset.tag_mask("water");
+    let settings = common::settings(24);
+    let graph = grid_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+    let mut task = WfcTask {
+        graph,
+        tileset: Arc::new(tileset),
+        seed: 3,
+        metadata: None,
+        settings: WfcSettings {
+            counts: vec![TileCount {
+                tiles: water.tile_iter().collect(),
+                max: 0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+        update_channel: None,
+    };
+    SingleThreaded::execute(&mut task).unwrap();
+
+    for tiles in task.graph.tiles.iter() {
+        assert!(!water.contains(tiles.collapse().unwrap()));
+    }
+}
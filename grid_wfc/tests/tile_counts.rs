@@ -0,0 +1,206 @@
+mod common;
+
+use bevy::math::{IRect, IVec2};
+use core_wfc::{
+    graph_chunker::{GraphChunker, PartitionSettings},
+    wfc_backend::{MultiThreaded, SingleThreaded},
+    wfc_task::{BacktrackingSettings, TileCount, WfcSettings},
+    TileSet, WaveFunction, WfcTask,
+};
+use grid_wfc::{
+    carcassonne_tileset::CarcassonneTileset,
+    grid_graph,
+    infinite_world::InfiniteWorld,
+    single_shot,
+    world::{ChunkSettings, GenerationMode},
+};
+use std::sync::Arc;
+
+fn counts() -> Vec<TileCount> {
+    vec![
+        // exactly one
+        TileCount {
+            tiles: vec![5],
+            min: 1,
+            max: 1,
+            ..Default::default()
+        },
+        // none at all
+        TileCount {
+            tiles: vec![0, 30, 60, 90],
+            max: 0,
+            ..Default::default()
+        },
+        // at least a few
+        TileCount {
+            tiles: vec![12, 42],
+            min: 6,
+            ..Default::default()
+        },
+    ]
+}
+
+fn count(tiles: impl Iterator<Item = WaveFunction>, group: &[usize]) -> usize {
+    tiles
+        .filter(|tile| group.contains(&tile.collapse().unwrap()))
+        .count()
+}
+
+#[test]
+fn single_shot_respects_counts() {
+    let tileset = Arc::new(CarcassonneTileset::default());
+    let graph = grid_graph::create(
+        &common::settings(16),
+        WaveFunction::filled(tileset.tile_count()),
+    );
+    let mut task = WfcTask {
+        graph,
+        tileset,
+        seed: 4,
+        metadata: None,
+        settings: WfcSettings {
+            counts: counts(),
+            ..Default::default()
+        },
+        update_channel: None,
+    };
+    SingleThreaded::execute(&mut task).unwrap();
+
+    let tiles = || task.graph.tiles.iter().cloned();
+    assert_eq!(count(tiles(), &[5]), 1);
+    assert_eq!(count(tiles(), &[0, 30, 60, 90]), 0);
+    assert!(count(tiles(), &[12, 42]) >= 6);
+}
+
+#[test]
+fn chunked_world_splits_counts() {
+    let (world, result) = single_shot::generate_world(
+        Arc::new(CarcassonneTileset::default()),
+        &mut MultiThreaded::new(4),
+        common::settings(32),
+        2,
+        GenerationMode::Deterministic,
+        ChunkSettings::default(),
+        WfcSettings {
+            counts: counts(),
+            ..Default::default()
+        },
+    );
+    result.unwrap();
+
+    let tiles = || world.world.iter().flatten().cloned();
+    assert_eq!(count(tiles(), &[5]), 1);
+    assert_eq!(count(tiles(), &[0, 30, 60, 90]), 0);
+    assert!(count(tiles(), &[12, 42]) >= 6);
+}
+
+#[test]
+fn regenerated_region_keeps_counts() {
+    let (mut world, result) = single_shot::generate_world(
+        Arc::new(CarcassonneTileset::default()),
+        &mut MultiThreaded::new(4),
+        common::settings(32),
+        2,
+        GenerationMode::Deterministic,
+        ChunkSettings::default(),
+        WfcSettings {
+            counts: counts(),
+            ..Default::default()
+        },
+    );
+    result.unwrap();
+
+    // the only tile 5 of the world is inside of the region
+    let index = world
+        .world
+        .iter()
+        .flatten()
+        .position(|tile| tile.collapse() == Some(5))
+        .unwrap();
+    let pos = IVec2::new(index as i32 / 32, index as i32 % 32);
+    for seed in 0..4 {
+        world.regenerate_region(pos - 2, pos + 3, 1, seed).unwrap();
+        let tiles = || world.world.iter().flatten().cloned();
+        assert_eq!(count(tiles(), &[5]), 1);
+        assert_eq!(count(tiles(), &[0, 30, 60, 90]), 0);
+        assert!(count(tiles(), &[12, 42]) >= 6);
+    }
+}
+
+#[test]
+fn graph_chunker_splits_counts() {
+    let tileset = Arc::new(CarcassonneTileset::default());
+    let settings = common::settings(32);
+    let graph = grid_graph::create(&settings, WaveFunction::filled(tileset.tile_count()));
+    let mut chunker = GraphChunker::new(
+        graph,
+        grid_graph::partition(&settings, 8),
+        tileset,
+        PartitionSettings::default(),
+        WfcSettings {
+            counts: counts(),
+            ..Default::default()
+        },
+        2,
+    )
+    .unwrap();
+    let mut backend = MultiThreaded::new(4);
+    chunker.start(&mut backend).unwrap();
+    chunker.run_to_completion(&mut backend).unwrap();
+
+    let tiles = || chunker.graph.tiles.iter().cloned();
+    assert_eq!(count(tiles(), &[5]), 1);
+    assert_eq!(count(tiles(), &[0, 30, 60, 90]), 0);
+    assert!(count(tiles(), &[12, 42]) >= 6);
+}
+
+#[test]
+fn infinite_world_rejects_counts() {
+    let mut world = InfiniteWorld::new(
+        Arc::new(CarcassonneTileset::default()),
+        ChunkSettings::default(),
+        WfcSettings {
+            counts: counts(),
+            ..Default::default()
+        },
+        0,
+        GenerationMode::Deterministic,
+    );
+    let region = IRect::new(0, 0, 16, 16);
+    assert!(world
+        .generate_region(&mut SingleThreaded::new(), region)
+        .is_err());
+    assert!(world.generated_chunks.is_empty());
+}
+
+#[test]
+fn impossible_counts_report_the_reason() {
+    let tileset = Arc::new(CarcassonneTileset::default());
+    let graph = grid_graph::create(
+        &common::settings(4),
+        WaveFunction::filled(tileset.tile_count()),
+    );
+    let mut task = WfcTask {
+        graph,
+        tileset,
+        seed: 0,
+        metadata: None,
+        settings: WfcSettings {
+            backtracking: BacktrackingSettings::Disabled,
+            counts: vec![TileCount {
+                tiles: vec![5],
+                min: 17,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+        update_channel: None,
+    };
+
+    let error = SingleThreaded::execute(&mut task).unwrap_err().to_string();
+    assert_eq!(
+        error,
+        "Invalid initial state: Tile count of [5] can't be reached, 16 tiles possible but at least \
+         17 required"
+    );
+}
@@ -1,5 +1,5 @@
 use crate::world::{GenerateEvent, MaybeWorld};
-use bevy::{asset::FileAssetIo, math::ivec2, prelude::*};
+use bevy::{asset::FileAssetIo, math::ivec2, prelude::*, utils::HashMap, window::PrimaryWindow};
 use bevy_inspector_egui::{
     bevy_egui::{EguiContexts, EguiPlugin},
     egui::{
@@ -8,16 +8,17 @@ use bevy_inspector_egui::{
     reflect_inspector::ui_for_value,
     DefaultInspectorConfigPlugin,
 };
-use core_wfc::{wfc_task::WfcSettings, TileRender, TileSet};
+use bevy_pancam::PanCam;
+use core_wfc::{wfc_task::WfcSettings, TileRender, TileSet, WaveFunction};
 use grid_wfc::{
     basic_tileset::BasicTileset,
     carcassonne_tileset::CarcassonneTileset,
     grid_graph::GridGraphSettings,
     mxgmn_tileset::MxgmnTileset,
-    overlapping_tileset::OverlappingTileset,
-    world::{ChunkSettings, ChunkState},
+    overlapping_tileset::{OverlappingSettings, OverlappingTileset},
+    world::{tiles_rendered_as, ChunkSettings, ChunkState},
 };
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use std::sync::Arc;
 
 pub struct UiPlugin;
@@ -31,7 +32,10 @@ impl Plugin for UiPlugin {
             .init_resource::<UiSettings>()
             .register_type::<UiSettings>()
             .register_type::<GridGraphSettings>()
-            .add_systems(Update, (ui, render_world, debug_gizmos).chain());
+            .add_systems(
+                Update,
+                (ui, use_brush, render_world, debug_gizmos, draw_painting).chain(),
+            );
     }
 }
 
@@ -44,6 +48,8 @@ struct UiSettings {
     multithreaded: bool,
     chunk_settings: ChunkSettings,
     wfc_settings: WfcSettings,
+    /// Cells around a regenerated region that are generated again as well
+    region_halo: usize,
     draw_gizmos: bool,
 }
 
@@ -57,11 +63,24 @@ impl Default for UiSettings {
             multithreaded: true,
             chunk_settings: Default::default(),
             wfc_settings: Default::default(),
+            region_halo: 1,
             draw_gizmos: false,
         }
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Brush {
+    Off,
+    /// Restricts cells to every tile rendered as this render tile
+    Paint(usize),
+    /// Restricts cells to the tiles with the n-th tag of the tileset
+    PaintTag(usize),
+    Erase,
+    /// Drag a rectangle to generate it again
+    Region,
+}
+
 #[derive(Resource)]
 struct UiState {
     picked_tileset: usize,
@@ -69,6 +88,11 @@ struct UiState {
     weights: Vec<f32>,
     tile_render_assets: Vec<Bleh>,
     tile_entities: Vec<Vec<Entity>>,
+    brush: Brush,
+    /// Cells painted with the brush, applied to the next generated world
+    painted: HashMap<IVec2, WaveFunction>,
+    /// First and last cell of the region being dragged
+    selection: Option<(IVec2, IVec2)>,
 }
 
 impl Default for UiState {
@@ -125,12 +149,17 @@ impl Default for UiState {
         let xml = std::fs::read_to_string(base_path.join("assets/samples.xml")).unwrap();
         let samples: Samples = serde_xml_rs::from_str(&xml).unwrap();
         for sample in samples.overlapping.into_iter() {
-            let overlap = sample.n / 2;
+            let settings = OverlappingSettings {
+                overlap: sample.n / 2,
+                symmetry: sample.symmetry,
+                periodic_input: sample.periodic_input,
+                ground: sample.ground,
+                full_neighborhood: true,
+            };
             tile_sets.push((
                 Arc::new(OverlappingTileset::from_image(
                     &base_path.join(format!("assets/samples/{}.png", sample.name)),
-                    overlap,
-                    sample.symmetry,
+                    &settings,
                 )),
                 format!("{} {} {}", sample.name, sample.n, sample.symmetry),
             ));
@@ -142,6 +171,9 @@ impl Default for UiState {
             weights: Vec::new(),
             tile_render_assets: Vec::new(),
             tile_entities: Vec::new(),
+            brush: Brush::Off,
+            painted: HashMap::new(),
+            selection: None,
         }
     }
 }
@@ -164,6 +196,10 @@ fn ui(
     if ui_state.weights.len() != tileset.tile_count() {
         ui_state.weights = tileset.get_weights().as_ref().clone();
 
+        // painted tiles belong to the previous tileset
+        ui_state.painted.clear();
+        ui_state.brush = Brush::Off;
+
         for handle in ui_state.tile_render_assets.drain(..) {
             if let Bleh::Image { bevy_handle, .. } = &handle {
                 contexts.remove_image(bevy_handle);
@@ -227,6 +263,7 @@ fn ui(
                                 tileset,
                                 settings: ui_settings.graph_settings.clone(),
                                 wfc_settings: ui_settings.wfc_settings.clone(),
+                                painted: ui_state.painted.clone(),
                                 seed,
                             });
                         } else if ui.button("Generate Chunked").clicked() {
@@ -244,6 +281,7 @@ fn ui(
                                 chunk_settings: ui_settings.chunk_settings,
                                 multithreaded: ui_settings.multithreaded,
                                 deterministic: ui_settings.deterministic,
+                                painted: ui_state.painted.clone(),
                                 seed,
                             });
                         } else if ui.button("Clear Output").clicked() {
@@ -251,6 +289,66 @@ fn ui(
                         }
                     });
 
+                CollapsingHeader::new("Brush")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.selectable_value(&mut ui_state.brush, Brush::Off, "Off");
+                            ui.selectable_value(&mut ui_state.brush, Brush::Erase, "Erase");
+                            ui.selectable_value(&mut ui_state.brush, Brush::Region, "Re-roll");
+                            if ui.button("Clear Painting").clicked() {
+                                ui_state.painted.clear();
+                            }
+                        });
+
+                        ui.label("Restrict cells to tiles that look like:");
+                        egui::Grid::new("brush_tiles").show(ui, |ui| {
+                            for i in 0..ui_state.tile_render_assets.len() {
+                                let selected = ui_state.brush == Brush::Paint(i);
+                                let response = match &ui_state.tile_render_assets[i] {
+                                    Bleh::Image { egui_handle, .. } => ui.add(
+                                        egui::ImageButton::new(*egui_handle, [32.0, 32.0])
+                                            .selected(selected),
+                                    ),
+                                    Bleh::Color(color) => {
+                                        let [r, g, b, _] = color.as_rgba_u8();
+                                        ui.add(
+                                            egui::Button::new("")
+                                                .fill(Color32::from_rgb(r, g, b))
+                                                .min_size([32.0, 32.0].into())
+                                                .stroke(egui::Stroke::new(
+                                                    if selected { 2.0 } else { 0.0 },
+                                                    Color32::WHITE,
+                                                )),
+                                        )
+                                    }
+                                };
+                                if response.on_hover_text(format!("Tile {}", i)).clicked() {
+                                    ui_state.brush = Brush::Paint(i);
+                                }
+
+                                if i % 8 == 7 {
+                                    ui.end_row();
+                                }
+                            }
+                        });
+
+                        let tileset = ui_state.tile_sets[ui_state.picked_tileset].0.clone();
+                        let tags = tileset.get_tags();
+                        if !tags.is_empty() {
+                            ui.label("Or to tiles tagged:");
+                            ui.horizontal_wrapped(|ui| {
+                                for (i, tag) in tags.keys().enumerate() {
+                                    ui.selectable_value(
+                                        &mut ui_state.brush,
+                                        Brush::PaintTag(i),
+                                        tag.as_str(),
+                                    );
+                                }
+                            });
+                        }
+                    });
+
                 // CollapsingHeader::new("Tileset Settings")
                 //     .default_open(true)
                 //     .show(ui, |ui| {
@@ -315,6 +413,108 @@ fn debug_gizmos(mut gizmos: Gizmos, world: Res<MaybeWorld>, ui_settings: Res<UiS
     }
 }
 
+/// Paints the cell under the cursor while the left mouse button is held or drags a region to
+/// generate again, panning is disabled while a brush is picked
+#[allow(clippy::too_many_arguments)]
+fn use_brush(
+    mut contexts: EguiContexts,
+    mut ui_state: ResMut<UiState>,
+    mut ui_settings: ResMut<UiSettings>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut pan_cams: Query<&mut PanCam>,
+    mut generate_events: EventWriter<GenerateEvent>,
+) {
+    for mut pan_cam in pan_cams.iter_mut() {
+        pan_cam.enabled = ui_state.brush == Brush::Off;
+    }
+
+    if ui_state.brush == Brush::Off || contexts.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+    let Some(position) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+
+    // inverse of the tile placement in render_world
+    let settings = &ui_settings.graph_settings;
+    let size = IVec2::new(settings.width as i32, settings.height as i32);
+    let cell = ((position + 0.5) * settings.height as f32)
+        .floor()
+        .as_ivec2();
+    let outside = cell.cmplt(IVec2::ZERO).any() || cell.cmpge(size).any();
+
+    match ui_state.brush {
+        Brush::Off => {}
+        Brush::Paint(render_tile) if mouse.pressed(MouseButton::Left) && !outside => {
+            let tileset = ui_state.tile_sets[ui_state.picked_tileset].0.clone();
+            let tiles = tiles_rendered_as(tileset.as_ref(), render_tile);
+            ui_state.painted.insert(cell, tiles);
+        }
+        Brush::PaintTag(tag) if mouse.pressed(MouseButton::Left) && !outside => {
+            let tileset = ui_state.tile_sets[ui_state.picked_tileset].0.clone();
+            if let Some(tiles) = tileset.get_tags().values().nth(tag) {
+                ui_state.painted.insert(cell, tiles.clone());
+            }
+        }
+        Brush::Erase if mouse.pressed(MouseButton::Left) && !outside => {
+            ui_state.painted.remove(&cell);
+        }
+        Brush::Paint(_) | Brush::PaintTag(_) | Brush::Erase => {}
+        Brush::Region => {
+            // regions dragged past the edge end at the edge
+            let cell = cell.clamp(IVec2::ZERO, size - IVec2::ONE);
+            if mouse.just_pressed(MouseButton::Left) {
+                ui_state.selection = Some((cell, cell));
+            } else if let Some((start, _)) = ui_state.selection {
+                ui_state.selection = Some((start, cell));
+            }
+
+            if mouse.just_released(MouseButton::Left) {
+                if let Some((start, end)) = ui_state.selection.take() {
+                    let seed = if !ui_settings.random_seed {
+                        ui_settings.seed
+                    } else {
+                        ui_settings.seed = rand::random();
+                        ui_settings.seed
+                    };
+
+                    generate_events.send(GenerateEvent::Region {
+                        bottom_left: start.min(end),
+                        top_right: start.max(end) + IVec2::ONE,
+                        halo: ui_settings.region_halo,
+                        seed,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn draw_painting(mut gizmos: Gizmos, ui_state: Res<UiState>, ui_settings: Res<UiSettings>) {
+    let height = ui_settings.graph_settings.height as f32;
+    for pos in ui_state.painted.keys() {
+        let center = (pos.as_vec2() + 0.5) / height - 0.5;
+        gizmos.rect_2d(center, 0.0, Vec2::splat(1.0 / height), Color::YELLOW);
+    }
+
+    if let Some((start, end)) = ui_state.selection {
+        let bottom_left = start.min(end).as_vec2() / height - 0.5;
+        let top_right = (start.max(end) + IVec2::ONE).as_vec2() / height - 0.5;
+        let center = (bottom_left + top_right) / 2.0;
+        gizmos.rect_2d(center, 0.0, top_right - bottom_left, Color::WHITE);
+    }
+}
+
 #[derive(Event)]
 pub struct RenderUpdateEvent;
 
@@ -390,6 +590,10 @@ fn render_world(
                                 bevy_handle,
                                 ..
                             } => {
+                                // big tiles move their sprite to the middle of the footprint
+                                transform.translation += (new_transform.translation.truncate()
+                                    / world_size.y as f32)
+                                    .extend(0.0);
                                 transform.rotation = new_transform.rotation;
                                 transform.scale = new_transform.scale;
                                 texture = bevy_handle.clone();
@@ -435,6 +639,11 @@ fn render_world(
                     let (_, mut transform, mut image_handle, mut sprite) = tile_entity_query
                         .get_mut(ui_state.tile_entities[x][y])
                         .unwrap();
+                    // reset what a big tile on this cell may have changed
+                    let pos = Vec2::new(x as f32, y as f32);
+                    transform.translation =
+                        ((pos + 0.5) / current_size.y as f32 - 0.5).extend(-0.5);
+                    transform.scale = Vec3::ONE;
 
                     if let Some(pattern_index) = world[x][y].collapse() {
                         let tile_index = tileset.get_render_tile(pattern_index);
@@ -444,6 +653,9 @@ fn render_world(
                                 bevy_handle,
                                 ..
                             } => {
+                                transform.translation += (new_transform.translation.truncate()
+                                    / current_size.y as f32)
+                                    .extend(0.0);
                                 transform.rotation = new_transform.rotation;
                                 transform.scale = new_transform.scale;
                                 *image_handle = bevy_handle.clone();
@@ -478,6 +690,35 @@ struct Overlapping {
     name: String,
     #[serde(rename = "N")]
     n: usize,
-    #[serde(default)]
+    #[serde(default = "default_symmetry")]
     symmetry: usize,
+    #[serde(default, deserialize_with = "deserialize_bool")]
+    ground: bool,
+    #[serde(
+        rename = "periodicInput",
+        default = "default_periodic_input",
+        deserialize_with = "deserialize_bool"
+    )]
+    periodic_input: bool,
+}
+
+fn default_symmetry() -> usize {
+    8
+}
+
+fn default_periodic_input() -> bool {
+    true
+}
+
+/// samples.xml uses C# style booleans (`True`/`False`) which serde_xml_rs does not accept
+fn deserialize_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    match value.to_lowercase().as_str() {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(serde::de::Error::custom(format!(
+            "invalid boolean: {}",
+            value
+        ))),
+    }
 }
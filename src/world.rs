@@ -2,15 +2,16 @@ use crate::ui::RenderUpdateEvent;
 use bevy::{prelude::*, utils::HashMap};
 use core_wfc::{
     wfc_backend::{Backend, MultiThreaded, SingleThreaded},
-    wfc_task::{Metadata, WfcSettings},
-    TileSet, WaveFunction, WfcTask,
+    wfc_task::{InvalidInitialState, WfcSettings},
+    Graph, TileSet, WaveFunction, WfcTask,
 };
 use crossbeam::channel;
 use grid_wfc::{
+    chunked_generator::{ChunkTaskData, ChunkedGenerator},
     grid_graph::GridGraphSettings,
-    world::{ChunkSettings, ChunkState, ChunkType, GenerationMode, World},
+    overlapping_tileset::OverlappingTileset,
+    world::{ChunkSettings, ChunkState, GenerationMode, World},
 };
-use rand::{rngs::SmallRng, SeedableRng};
 use std::sync::Arc;
 
 pub struct WorldPlugin;
@@ -20,7 +21,7 @@ impl Plugin for WorldPlugin {
         app.add_event::<GenerateEvent>()
             .init_resource::<Backends>()
             .init_resource::<MaybeWorld>()
-            .init_resource::<Failed>()
+            .init_resource::<Generator>()
             .add_systems(Update, (handle_events, handle_output).chain());
     }
 }
@@ -31,6 +32,7 @@ pub enum GenerateEvent {
         tileset: Arc<dyn TileSet>,
         settings: GridGraphSettings,
         wfc_settings: WfcSettings,
+        painted: HashMap<IVec2, WaveFunction>,
         seed: u64,
     },
     Chunked {
@@ -40,6 +42,14 @@ pub enum GenerateEvent {
         chunk_settings: ChunkSettings,
         multithreaded: bool,
         deterministic: bool,
+        painted: HashMap<IVec2, WaveFunction>,
+        seed: u64,
+    },
+    /// Generates a region of the current world again, see `World::regenerate_region`
+    Region {
+        bottom_left: IVec2,
+        top_right: IVec2,
+        halo: usize,
         seed: u64,
     },
     Reset,
@@ -65,29 +75,27 @@ impl Default for Backends {
     }
 }
 
-enum TaskData {
-    Single { size: IVec2 },
-    Chunked { chunk: IVec2, chunk_type: ChunkType },
+struct SingleTaskData {
+    size: IVec2,
 }
 
 #[derive(Resource, Deref, DerefMut, Default)]
 pub struct MaybeWorld(Option<World>);
 
+/// Scheduler of the current world, only set for chunked generation
 #[derive(Resource, Deref, DerefMut, Default)]
-struct Failed(bool);
+struct Generator(Option<ChunkedGenerator>);
 
 fn handle_events(
     mut render_world_event: EventWriter<RenderUpdateEvent>,
     mut generate_event: EventReader<GenerateEvent>,
     mut backends: ResMut<Backends>,
     mut world: ResMut<MaybeWorld>,
-    mut failed: ResMut<Failed>,
+    mut generator: ResMut<Generator>,
 ) {
     for generate_event in generate_event.iter() {
         let generate_event = generate_event.clone();
 
-        failed.0 = false;
-
         match generate_event {
             GenerateEvent::Chunked {
                 tileset,
@@ -95,22 +103,20 @@ fn handle_events(
                 wfc_settings,
                 multithreaded,
                 deterministic,
+                painted,
                 seed,
                 chunk_settings,
             } => {
-                let filled = WaveFunction::filled(tileset.tile_count());
-                let rng = SmallRng::seed_from_u64(seed);
-                let update_channel = wfc_settings.progress_updates.map(|_| channel::unbounded());
-                let mut new_world = World {
-                    world: vec![vec![filled; settings.height]; settings.width],
-                    generated_chunks: HashMap::new(),
+                let mut new_world = World::new(
+                    tileset.clone(),
+                    &settings,
                     chunk_settings,
-                    tileset: tileset.clone(),
-                    rng,
-                    outstanding: 0,
-                    settings: wfc_settings.clone(),
-                    update_channel,
-                };
+                    wfc_settings.clone(),
+                    seed,
+                );
+                new_world.painted = painted;
+                new_world.update_channel =
+                    wfc_settings.progress_updates.map(|_| channel::unbounded());
 
                 let generation_mode = match deterministic {
                     true => GenerationMode::Deterministic,
@@ -126,71 +132,93 @@ fn handle_events(
 
                 backend.clear();
 
-                let start_chunks = new_world.start_generation(generation_mode);
-                let update_channel = new_world.update_channel.as_ref().map(|c| c.0.clone());
-                for (chunk, chunk_type) in start_chunks {
-                    new_world
-                        .generated_chunks
-                        .insert(chunk, ChunkState::Scheduled);
-                    let graph = new_world.extract_chunk(chunk);
-                    let seed = seed + chunk.x as u64 * 1000 + chunk.y as u64;
-                    let metadata: Metadata =
-                        Some(Arc::new(TaskData::Chunked { chunk, chunk_type }));
-
-                    let task = WfcTask {
-                        graph,
-                        tileset: new_world.tileset.clone(),
-                        seed,
-                        metadata,
-                        settings: wfc_settings.clone(),
-                        update_channel: update_channel.clone(),
+                *generator = Generator(None);
+                let new_generator =
+                    match ChunkedGenerator::start(&mut new_world, backend, generation_mode) {
+                        Ok(new_generator) => new_generator,
+                        Err(e) => {
+                            error!("Error while generating world: {:?}", e);
+                            continue;
+                        }
                     };
 
-                    new_world.outstanding += 1;
-                    backend.queue_task(task).unwrap();
-                }
-
                 *world = MaybeWorld(Some(new_world));
+                *generator = Generator(Some(new_generator));
             }
             GenerateEvent::Single {
                 tileset,
                 settings,
                 wfc_settings,
+                painted,
                 seed,
             } => {
+                let mut new_world = World::new(
+                    tileset.clone(),
+                    &settings,
+                    ChunkSettings::default(),
+                    wfc_settings.clone(),
+                    seed,
+                );
+                new_world.painted = painted;
+                new_world.update_channel =
+                    wfc_settings.progress_updates.map(|_| channel::unbounded());
+                let sender = new_world.update_channel.as_ref().map(|c| c.0.clone());
+                // the whole world is a single task, its cells are filled in once it finishes
+                new_world.world =
+                    vec![vec![WaveFunction::empty(); settings.height]; settings.width];
+                new_world
+                    .generated_chunks
+                    .insert(IVec2::ZERO, ChunkState::Done);
+                new_world.outstanding = 1;
+
                 let filled = WaveFunction::filled(tileset.tile_count());
-                let graph = grid_wfc::grid_graph::create(&settings, filled);
+                let mut graph = match tileset.as_any().downcast_ref::<OverlappingTileset>() {
+                    Some(tileset) => tileset.create_graph(&settings, filled),
+                    None => grid_wfc::grid_graph::create(&settings, filled),
+                };
                 let size = IVec2::new(settings.width as i32, settings.height as i32);
-                let update_channel = wfc_settings.progress_updates.map(|_| channel::unbounded());
-                let sender = update_channel.as_ref().map(|c| c.0.clone());
+                new_world.apply_borders(&mut graph, IVec2::ZERO, size);
+                new_world.apply_ground(&mut graph, IVec2::ZERO, size);
+                new_world.apply_painting(&mut graph, IVec2::ZERO, size);
                 let task = WfcTask {
                     graph,
                     tileset: tileset.clone(),
                     seed,
-                    metadata: Some(Arc::new(TaskData::Single { size })),
+                    metadata: Some(Arc::new(SingleTaskData { size })),
                     settings: wfc_settings.clone(),
                     update_channel: sender,
                 };
 
                 backends.single_threaded.clear();
 
+                *generator = Generator(None);
                 backends.multithreaded = false;
                 backends.single_threaded.queue_task(task).unwrap();
 
-                let rng = SmallRng::seed_from_u64(seed);
-                let new_world = World {
-                    world: vec![vec![WaveFunction::empty(); settings.height]; settings.width],
-                    generated_chunks: HashMap::from_iter(vec![(IVec2::ZERO, ChunkState::Done)]),
-                    chunk_settings: ChunkSettings::default(),
-                    tileset: tileset.clone(),
-                    rng: rng.clone(),
-                    outstanding: 0,
-                    settings: wfc_settings.clone(),
-                    update_channel,
-                };
                 *world = MaybeWorld(Some(new_world));
             }
+            GenerateEvent::Region {
+                bottom_left,
+                top_right,
+                halo,
+                seed,
+            } => {
+                let world = match world.as_mut().as_mut() {
+                    Some(world) => world,
+                    None => continue,
+                };
+                if world.outstanding > 0 {
+                    warn!("Can't regenerate a region while the world is being generated");
+                    continue;
+                }
+
+                if let Err(e) = world.regenerate_region(bottom_left, top_right, halo, seed) {
+                    error!("Failed to regenerate region: {:?}", e);
+                }
+                render_world_event.send(RenderUpdateEvent);
+            }
             GenerateEvent::Reset => {
+                *generator = Generator(None);
                 *world = MaybeWorld(None);
                 render_world_event.send(RenderUpdateEvent);
             }
@@ -201,8 +229,8 @@ fn handle_events(
 fn handle_output(
     mut backends: ResMut<Backends>,
     mut world: ResMut<MaybeWorld>,
+    mut generator: ResMut<Generator>,
     mut render_world_event: EventWriter<RenderUpdateEvent>,
-    mut failed: ResMut<Failed>,
 ) {
     let backend: &mut dyn Backend = if backends.multithreaded {
         &mut backends.multi_threaded
@@ -210,106 +238,63 @@ fn handle_output(
         &mut backends.single_threaded
     };
 
-    if let Some(world) = world.as_mut().as_mut() {
-        if let Some((_, update_receiver)) = world.update_channel.clone() {
-            let mut update = None;
-            while let Ok(yep) = update_receiver.try_recv() {
-                update = Some(yep);
-            }
-
-            if let Some((graph, metadata)) = update {
-                match metadata.unwrap().downcast_ref().unwrap() {
-                    TaskData::Chunked { chunk, .. } => {
-                        world.merge_chunk(*chunk, graph);
-                    }
-                    TaskData::Single { size } => {
-                        // Note: Assumes that the graph is a grid graph with a standard ordering
-                        let mut new_world =
-                            vec![vec![WaveFunction::empty(); size.y as usize]; size.x as usize];
-                        for x in 0..size.x {
-                            for y in 0..size.y {
-                                new_world[x as usize][y as usize] =
-                                    graph.tiles[y as usize * size.x as usize + x as usize].clone();
-                            }
-                        }
+    let world = match world.as_mut().as_mut() {
+        Some(world) => world,
+        None => return,
+    };
 
-                        world.world = new_world;
-                    }
-                }
+    if let Some((_, update_receiver)) = world.update_channel.clone() {
+        let mut update = None;
+        while let Ok(yep) = update_receiver.try_recv() {
+            update = Some(yep);
+        }
 
-                render_world_event.send(RenderUpdateEvent);
+        if let Some((graph, metadata)) = update {
+            let metadata = metadata.unwrap();
+            if let Some(ChunkTaskData { chunk, discard, .. }) = metadata.downcast_ref() {
+                world.merge_chunk(*chunk, *discard, graph);
+            } else if let Some(SingleTaskData { size }) = metadata.downcast_ref() {
+                world.world = to_world(graph, *size);
             }
+
+            render_world_event.send(RenderUpdateEvent);
         }
     }
 
-    while let Some((task, error)) = backend.get_output() {
-        let world = world.as_mut().as_mut().unwrap();
-        world.outstanding -= 1;
-
-        if failed.0 {
-            continue;
+    if let Some(generator) = generator.as_mut().as_mut() {
+        if generator.poll(world, backend).unwrap() > 0 {
+            render_world_event.send(RenderUpdateEvent);
         }
+        return;
+    }
 
-        match task.metadata.as_ref().unwrap().downcast_ref().unwrap() {
-            TaskData::Chunked { chunk, chunk_type } => {
-                world.merge_chunk(*chunk, task.graph);
-                if error.is_err() {
-                    error!("Error while generating world: {:?}", error);
-
-                    failed.0 = true;
-                    world.generated_chunks.insert(*chunk, ChunkState::Failed);
-
-                    render_world_event.send(RenderUpdateEvent);
-
-                    continue;
-                }
-
-                world.generated_chunks.insert(*chunk, ChunkState::Done);
-
-                let ready = world.process_chunk(*chunk, *chunk_type);
-
-                for (chunk, chunk_type) in ready {
-                    world.generated_chunks.insert(chunk, ChunkState::Scheduled);
-                    let graph = world.extract_chunk(chunk);
-                    let seed = chunk.x as u64 * 1000 + chunk.y as u64;
-                    let metadata: Metadata =
-                        Some(Arc::new(TaskData::Chunked { chunk, chunk_type }));
-
-                    let update_channel = world.update_channel.as_ref().map(|c| c.0.clone());
-                    let task = WfcTask {
-                        graph,
-                        tileset: world.tileset.clone(),
-                        seed,
-                        metadata,
-                        settings: world.settings.clone(),
-                        update_channel,
-                    };
-
-                    world.outstanding += 1;
-                    backend.queue_task(task).unwrap();
-                }
-
-                render_world_event.send(RenderUpdateEvent);
+    while let Some((task, error)) = backend.get_output() {
+        world.outstanding -= 1;
+        let SingleTaskData { size } = task.metadata.as_ref().unwrap().downcast_ref().unwrap();
+        if let Err(e) = error {
+            match e.downcast_ref::<InvalidInitialState>() {
+                Some(&InvalidInitialState { node }) => error!(
+                    "Error while generating world: Invalid initial state at cell {}",
+                    IVec2::new(node as i32 % size.x, node as i32 / size.x)
+                ),
+                None => error!("Error while generating world: {:?}", e),
             }
-            TaskData::Single { size } => {
-                if error.is_err() {
-                    error!("Error while generating world: {:?}", error);
-                }
+        }
 
-                // Note: Assumes that the graph is a grid graph with a standard ordering
-                let graph = task.graph;
-                let mut new_world =
-                    vec![vec![WaveFunction::empty(); size.y as usize]; size.x as usize];
-                for x in 0..size.x {
-                    for y in 0..size.y {
-                        new_world[x as usize][y as usize] =
-                            graph.tiles[y as usize * size.x as usize + x as usize].clone();
-                    }
-                }
+        world.world = to_world(task.graph, *size);
+        render_world_event.send(RenderUpdateEvent);
+    }
+}
 
-                world.world = new_world;
-                render_world_event.send(RenderUpdateEvent);
-            }
+/// Converts the graph of a single shot generation into world columns
+fn to_world(graph: Graph<WaveFunction>, size: IVec2) -> Vec<Vec<WaveFunction>> {
+    // Note: Assumes that the graph is a grid graph with a standard ordering
+    let mut new_world = vec![vec![WaveFunction::empty(); size.y as usize]; size.x as usize];
+    for x in 0..size.x {
+        for y in 0..size.y {
+            new_world[x as usize][y as usize] =
+                graph.tiles[y as usize * size.x as usize + x as usize].clone();
         }
     }
+    new_world
 }